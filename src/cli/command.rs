@@ -1,4 +1,4 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 
 /// Command-line interface structure for the gim tool, using clap for argument parsing.
 #[derive(Parser)]
@@ -31,6 +31,18 @@ pub struct GimCli {
     #[arg(long, default_value_t = false)]
     pub dry: bool,
 
+    /// Print the generated message to stdout instead of committing, other output goes to stderr
+    #[arg(long, default_value_t = false)]
+    pub print: bool,
+
+    /// Write the generated message to a file instead of committing
+    #[arg(short, long)]
+    pub output: Option<std::path::PathBuf>,
+
+    /// Output format; json prints one JSON object with the results to stdout, other output goes to stderr
+    #[arg(long, value_enum, global = true, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
     /// Custom diff prompt to override the default
     #[arg(long)]
     pub diff_prompt: Option<String>,
@@ -42,6 +54,26 @@ pub struct GimCli {
     /// Maximum number of changed files to send to AI (overrides config)
     #[arg(short = 'n', long)]
     pub max_files: Option<usize>,
+
+    /// Edit the generated message in the git editor before committing, like 'git commit -e'
+    #[arg(short, long, default_value_t = false)]
+    pub edit: bool,
+
+    /// Commit without reviewing the generated message
+    #[arg(short, long, default_value_t = false)]
+    pub yes: bool,
+
+    /// Number of candidate subjects to choose from (overrides config)
+    #[arg(short, long)]
+    pub candidates: Option<usize>,
+
+    /// Send the request even if it exceeds a hard budget of the config file
+    #[arg(long, default_value_t = false)]
+    pub ignore_budget: bool,
+
+    /// Ask the AI again instead of reusing cached answers
+    #[arg(long, default_value_t = false)]
+    pub no_cache: bool,
 }
 
 /// Enum representing all supported subcommands for the gim CLI.
@@ -83,6 +115,9 @@ pub enum GimCommands {
 
     /// Setup the ai-api configuration
     Ai {
+        #[command(subcommand)]
+        action: Option<AiCommands>,
+
         /// the ai model name
         #[arg(short, long)]
         model: Option<String>,
@@ -98,6 +133,10 @@ pub enum GimCommands {
         /// the answer language
         #[arg(short, long)]
         language: Option<String>,
+
+        /// stream the answer to the terminal while it is generated (true or false)
+        #[arg(long)]
+        stream: Option<bool>,
     },
 
     /// Setup the git configuration
@@ -110,8 +149,79 @@ pub enum GimCommands {
         #[arg(long)]
         max_files: Option<usize>,
 
+        /// Review the generated message before committing (true or false)
+        #[arg(long)]
+        review: Option<bool>,
+
         /// Print config file's location
         #[arg(long, default_value_t = false)]
         show_location: bool,
     },
+
+    /// Show the recorded ai token usage and estimated spend
+    Stats {
+        /// Group the usage by day, week or month
+        #[arg(short, long, value_enum, default_value_t = StatsPeriod::Day)]
+        by: StatsPeriod,
+
+        /// Number of most recent periods to show
+        #[arg(short = 'n', long, default_value_t = 7)]
+        last: usize,
+
+        /// Include every repository instead of the current one
+        #[arg(short, long, default_value_t = false)]
+        all: bool,
+    },
+
+    /// Manage the cache of ai answers
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommands,
+    },
+
+    /// Manage the prepare-commit-msg hook filling in the message of 'git commit'
+    Hook {
+        #[command(subcommand)]
+        action: HookCommands,
+    },
+}
+
+/// Formats the results of gim can be printed in.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// Period lengths `gim stats` can group by.
+#[derive(ValueEnum, Clone, Copy)]
+pub enum StatsPeriod {
+    Day,
+    Week,
+    Month,
+}
+
+/// Subcommands of `gim cache`.
+#[derive(Subcommand)]
+pub enum CacheCommands {
+    /// Remove every cached answer
+    Clear,
+}
+
+/// Subcommands of `gim hook`.
+#[derive(Subcommand)]
+pub enum HookCommands {
+    /// Install the hook, chaining a hook that is already there
+    Install,
+    /// Remove the hook and restore the chained one
+    Uninstall,
+    /// Show whether the hook is installed
+    Status,
+}
+
+/// Subcommands of `gim ai`.
+#[derive(Subcommand)]
+pub enum AiCommands {
+    /// List the effective provider registry, custom providers first
+    Providers,
 }
@@ -1,3 +1,5 @@
 pub mod command;
 
-pub use command::{GimCli, GimCommands};
+pub use command::{
+    AiCommands, CacheCommands, GimCli, GimCommands, HookCommands, OutputFormat, StatsPeriod,
+};
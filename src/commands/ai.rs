@@ -1,9 +1,34 @@
+use lazy_static::lazy_static;
 use std::io::Result;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 use toml;
 
-use crate::utils::output;
+use crate::core::ai::client::{self, Endpoint};
+use crate::core::ai::error::AiError;
+use crate::core::ai::provider::Registry;
+use crate::core::ai::provider::azure::{self, AzureConfig, AzureOpenAi};
+use crate::core::ai::provider::openai::matches_pattern;
+use crate::core::ai::provider::openai::{OpenAiCompatible, ProviderConfig};
+use crate::core::ai::retry::RetryPolicy;
+use crate::core::ai::tokens;
+use crate::core::ai::transport::Transport;
+use crate::utils::{output, report};
 
-/// Updates the AI configuration in the provided TOML value with the specified model, API key, URL, and language.
+static PROVIDERS_SECTION: &str = "providers";
+static FALLBACKS_KEY: &str = "fallbacks";
+static AZURE_KEY: &str = "azure";
+static CONTEXT_WINDOWS_KEY: &str = "context_windows";
+
+/// Summary requests in flight at the same time unless configured otherwise.
+const DEFAULT_CONCURRENCY: usize = 4;
+
+lazy_static! {
+    static ref CANDIDATES: AtomicUsize = AtomicUsize::new(0);
+}
+
+/// Updates the AI configuration in the provided TOML value with the specified model, API key, URL, language and streaming switch.
 ///
 /// # Arguments
 ///
@@ -12,12 +37,14 @@ use crate::utils::output;
 /// * `apikey` - Optional API key to set.
 /// * `url` - Optional API URL to set.
 /// * `language` - Optional language to set.
+/// * `stream` - Optional streaming switch to set.
 pub fn update_ai_config(
     config: &mut toml::Value,
     model: &Option<String>,
     apikey: &Option<String>,
     url: &Option<String>,
     language: &Option<String>,
+    stream: &Option<bool>,
 ) {
     let ai_table = config
         .get_mut("ai")
@@ -47,6 +74,10 @@ pub fn update_ai_config(
         );
     }
 
+    if let Some(stream_value) = stream {
+        ai_table.insert("stream".to_string(), toml::Value::Boolean(*stream_value));
+    }
+
     if let Err(e) = gim_config::config::save_config(config) {
         eprintln!("Failed to save AI info to file: {}", e)
     }
@@ -99,86 +130,478 @@ pub fn mask_api_key(api_key: &str) -> String {
 
 /// Validates and retrieves AI configuration.
 ///
-/// # Arguments
-///
-/// * `auto_add` - Whether auto-add is enabled.
-/// * `changed` - Whether there are changes.
+/// The API key may be omitted if the resolved provider does not need one. With an `[ai.azure]`
+/// table the url is derived from the deployment, which also serves as the default model name.
 ///
 /// # Returns
 ///
-/// * `Some((url, model_name, api_key, language))` if valid, `None` otherwise.
-pub fn get_validated_ai_config(
-    auto_add: bool,
-    changed: bool,
-) -> Option<(String, String, String, String)> {
-    let ai_config = get_ai_config();
-    if ai_config.is_err() {
-        ai_generating_error(
-            "Error: ai section is not configured, abort",
-            auto_add && changed,
-        );
-        return None;
-    }
-    let ai_config = match ai_config {
-        Ok(config) => config,
+/// * `Ok((url, model_name, api_key, language))` if valid.
+/// * `Err(AiError::ConfigMissing)` naming the first missing value otherwise.
+pub fn get_validated_ai_config() -> std::result::Result<(String, String, String, String), AiError> {
+    let ai_config = get_ai_config()
+        .map_err(|e| AiError::ConfigMissing(format!("ai section is not configured ({})", e)))?;
+    let get = |key: &str| {
+        ai_config
+            .get(key)
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_string())
+            .ok_or_else(|| AiError::ConfigMissing(format!("missing '{}' in ai section", key)))
+    };
+
+    let azure = get_azure_config(&ai_config)?;
+    let (url, model_name) = match azure {
+        Some(azure) => (
+            get("url")
+                .ok()
+                .filter(|url| !url.is_empty())
+                .unwrap_or_else(|| azure.url()),
+            get("model").unwrap_or(azure.deployment),
+        ),
+        None => (get("url")?, get("model")?),
+    };
+    let api_key = match get("apikey") {
+        Ok(v) => v,
         Err(e) => {
-            ai_generating_error(
-                &format!("Error: Failed to get AI config - {}", e),
-                auto_add && changed,
-            );
-            return None;
+            // Local servers such as Ollama work without a key
+            let keyless = get_provider_registry()
+                .resolve(&model_name, &url)
+                .is_some_and(|(provider, _)| !provider.requires_api_key());
+            if !keyless {
+                return Err(e);
+            }
+            String::new()
         }
     };
+    let language = get("language")?;
 
-    let url = match ai_config.get("url").and_then(|v| v.as_str()) {
-        Some(v) => v,
-        None => {
-            ai_generating_error("Error: Missing 'url' in AI config", auto_add && changed);
-            return None;
-        }
+    Ok((url, model_name, api_key, language))
+}
+
+/// Resolves the primary AI endpoint followed by the `[[ai.fallbacks]]` entries in order.
+///
+/// Fallback entries need a `model`; `url` and `apikey` are optional. Entries that cannot be
+/// resolved are skipped with a warning.
+///
+/// # Arguments
+///
+/// * `url` - The primary API url, may be empty to use the model's default.
+/// * `model_name` - The primary model name.
+/// * `api_key` - The primary API key.
+///
+/// # Returns
+///
+/// * `Ok(Vec<Endpoint>)` with the primary endpoint first.
+/// * `Err(AiError::ConfigMissing)` if the primary endpoint cannot be resolved.
+pub fn get_endpoints(
+    url: String,
+    model_name: String,
+    api_key: String,
+) -> std::result::Result<Vec<Endpoint>, AiError> {
+    let registry = get_provider_registry();
+    let retry = get_retry_policy();
+    let (connect_timeout, timeout) = get_timeouts();
+    let transport = get_transport(get_ai_config().ok())?;
+    let mut primary = Endpoint::resolve(&registry, url, model_name, api_key)?;
+    let azure_configured = match get_ai_config() {
+        Ok(ai) => get_azure_config(&ai)?.is_some(),
+        Err(_) => false,
     };
-    let model_name = match ai_config.get("model").and_then(|v| v.as_str()) {
-        Some(v) => v,
-        None => {
-            ai_generating_error("Error: Missing 'model' in AI config", auto_add && changed);
-            return None;
+    if azure_configured || primary.provider.name() == azure::NAME {
+        use_azure(&mut primary);
+    }
+    primary.transport = transport.overridden_by(&primary.transport);
+    primary.retry = retry.clone();
+    primary.connect_timeout = connect_timeout;
+    primary.timeout = timeout;
+    let mut endpoints = vec![primary];
+
+    let fallbacks = get_ai_config()
+        .ok()
+        .and_then(|ai| ai.get(FALLBACKS_KEY).and_then(|v| v.as_array()).cloned())
+        .unwrap_or_default();
+    for fallback in fallbacks {
+        let get = |key: &str| {
+            fallback
+                .get(key)
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string()
+        };
+        let azure = match get_azure_config(&fallback) {
+            Ok(azure) => azure,
+            Err(e) => {
+                output::print_warning(&format!("Ignore [[ai.{}]] entry: {}", FALLBACKS_KEY, e));
+                continue;
+            }
+        };
+        let mut url = get("url");
+        let mut model_name = get("model");
+        let is_azure = azure.is_some();
+        if let Some(azure) = azure {
+            if url.is_empty() {
+                url = azure.url();
+            }
+            if model_name.is_empty() {
+                model_name = azure.deployment;
+            }
         }
-    };
-    let api_key = match ai_config.get("apikey").and_then(|v| v.as_str()) {
-        Some(v) => v,
-        None => {
-            ai_generating_error("Error: Missing 'apikey' in AI config", auto_add && changed);
-            return None;
+        if model_name.is_empty() {
+            output::print_warning(&format!(
+                "Ignore [[ai.{}]] entry without 'model'",
+                FALLBACKS_KEY
+            ));
+            continue;
         }
-    };
-    let language = match ai_config.get("language").and_then(|v| v.as_str()) {
-        Some(v) => v,
-        None => {
-            ai_generating_error(
-                "Error: Missing 'language' in AI config",
-                auto_add && changed,
-            );
-            return None;
+        let resolved = Endpoint::resolve(&registry, url, model_name.clone(), get("apikey"))
+            .and_then(|endpoint| Ok((endpoint, get_transport(Some(fallback.clone()))?)));
+        match resolved {
+            Ok((mut endpoint, own_transport)) => {
+                if is_azure || endpoint.provider.name() == azure::NAME {
+                    use_azure(&mut endpoint);
+                }
+                endpoint.transport = transport
+                    .overridden_by(&endpoint.transport)
+                    .overridden_by(&own_transport);
+                endpoint.retry = retry.clone();
+                endpoint.connect_timeout = connect_timeout;
+                endpoint.timeout = timeout;
+                output::print_verbose(&format!(
+                    "Register fallback model '{}' at {}",
+                    endpoint.model, endpoint.url
+                ));
+                endpoints.push(endpoint);
+            }
+            Err(e) => {
+                output::print_warning(&format!("Ignore fallback model '{}': {}", model_name, e))
+            }
         }
+    }
+    Ok(endpoints)
+}
+
+/// Switches an endpoint to the Azure provider for the API version in its url.
+///
+/// The url alone selects Azure only on `*.openai.azure.com` hosts, while gateways, private
+/// endpoints and sovereign clouds configured by an `[ai.azure]` table serve deployments on other
+/// hosts.
+fn use_azure(endpoint: &mut Endpoint) {
+    endpoint.provider = Arc::new(AzureOpenAi::for_url(&endpoint.url));
+}
+
+/// Reads the proxy, root certificate and extra header settings of a config table.
+///
+/// # Arguments
+///
+/// * `table` - The ai section or an `[[ai.fallbacks]]` entry, `None` if not configured.
+///
+/// # Returns
+///
+/// * `Ok(Transport)` with the settings found, empty if there are none.
+/// * `Err(AiError::ConfigInvalid)` if the settings have the wrong type.
+pub fn get_transport(table: Option<toml::Value>) -> std::result::Result<Transport, AiError> {
+    let Some(table) = table else {
+        return Ok(Transport::default());
     };
+    let transport: Transport = table
+        .try_into()
+        .map_err(|e| AiError::ConfigInvalid(format!("proxy, ca_cert or headers ({})", e)))?;
+    if let Some(proxy) = &transport.proxy {
+        output::print_verbose(&format!("Using proxy {}", proxy));
+    }
+    Ok(transport)
+}
+
+/// Reads the `azure` table of the ai section or an `[[ai.fallbacks]]` entry.
+///
+/// # Returns
+///
+/// * `Ok(Some(AzureConfig))` if the table is present, `Ok(None)` otherwise.
+/// * `Err(AiError::ConfigInvalid)` if `resource` or `deployment` is missing.
+pub fn get_azure_config(table: &toml::Value) -> std::result::Result<Option<AzureConfig>, AiError> {
+    table
+        .get(AZURE_KEY)
+        .map(|azure| {
+            azure
+                .clone()
+                .try_into()
+                .map_err(|e| AiError::ConfigInvalid(format!("[ai.{}] ({})", AZURE_KEY, e)))
+        })
+        .transpose()
+}
+
+/// Returns the models of the `[[ai.fallbacks]]` entries in order.
+pub fn get_fallback_models() -> Vec<String> {
+    get_ai_config()
+        .ok()
+        .and_then(|ai| ai.get(FALLBACKS_KEY).and_then(|v| v.as_array()).cloned())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|fallback| fallback.get("model").and_then(|v| v.as_str()))
+        .map(|model| model.to_string())
+        .collect()
+}
+
+/// Returns whether AI answers are streamed to the terminal as they are generated.
+///
+/// # Returns
+///
+/// * The `stream` value of the ai section, `false` if not configured.
+pub fn get_stream_enabled() -> bool {
+    get_ai_config()
+        .ok()
+        .and_then(|ai| ai.get("stream").and_then(|v| v.as_bool()))
+        .unwrap_or(false)
+}
+
+/// Sets the number of candidate subjects for this run, overriding the config file.
+///
+/// # Arguments
+///
+/// * `candidates` - The number of candidates, `None` to use the config file.
+pub fn set_candidates(candidates: Option<usize>) {
+    CANDIDATES.store(candidates.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// Returns how many candidate subjects the user picks from.
+///
+/// # Returns
+///
+/// * The `--candidates` option, else the `candidates` value of the ai section, else 1.
+pub fn get_candidates() -> usize {
+    match CANDIDATES.load(Ordering::Relaxed) {
+        0 => get_ai_config()
+            .ok()
+            .and_then(|ai| ai.get("candidates").and_then(|v| v.as_integer()))
+            .and_then(|candidates| usize::try_from(candidates).ok())
+            .unwrap_or(1)
+            .max(1),
+        candidates => candidates,
+    }
+}
+
+/// Returns whether the file summaries and the subject are asked for in a single request.
+///
+/// # Returns
+///
+/// * The `single_request` value of the ai section, `false` if not configured.
+pub fn get_single_request_enabled() -> bool {
+    get_ai_config()
+        .ok()
+        .and_then(|ai| ai.get("single_request").and_then(|v| v.as_bool()))
+        .unwrap_or(false)
+}
+
+/// Returns whether the commit subject is asked for as a JSON commit message.
+///
+/// # Returns
+///
+/// * The `structured` value of the ai section, `false` if not configured.
+pub fn get_structured_enabled() -> bool {
+    get_ai_config()
+        .ok()
+        .and_then(|ai| ai.get("structured").and_then(|v| v.as_bool()))
+        .unwrap_or(false)
+}
 
-    Some((
-        url.to_string(),
-        model_name.to_string(),
-        api_key.to_string(),
-        language.to_string(),
-    ))
+/// Returns whether every changed file is summarized by a request of its own.
+///
+/// # Returns
+///
+/// * The `per_file` value of the ai section, `false` if not configured.
+pub fn get_per_file_enabled() -> bool {
+    get_ai_config()
+        .ok()
+        .and_then(|ai| ai.get("per_file").and_then(|v| v.as_bool()))
+        .unwrap_or(false)
+}
+
+/// Returns how many summary requests may be in flight at the same time.
+///
+/// # Returns
+///
+/// * The `concurrency` value of the ai section, with a default for missing or zero values.
+pub fn get_concurrency() -> usize {
+    get_ai_config()
+        .ok()
+        .and_then(|ai| ai.get("concurrency").and_then(|v| v.as_integer()))
+        .and_then(|concurrency| usize::try_from(concurrency).ok())
+        .filter(|concurrency| *concurrency > 0)
+        .unwrap_or(DEFAULT_CONCURRENCY)
 }
 
-/// Prints AI generation error message.
+/// Returns the timeouts for AI requests from the ai section.
+///
+/// # Returns
+///
+/// * `(connect_timeout, timeout)` from `connect_timeout_secs` and `timeout_secs`, with defaults
+///   for missing or zero values.
+pub fn get_timeouts() -> (Duration, Duration) {
+    let get_secs = |key: &str, default: Duration| {
+        get_ai_config()
+            .ok()
+            .and_then(|ai| ai.get(key).and_then(|v| v.as_integer()))
+            .filter(|secs| *secs > 0)
+            .map_or(default, |secs| Duration::from_secs(secs as u64))
+    };
+    (
+        get_secs("connect_timeout_secs", client::DEFAULT_CONNECT_TIMEOUT),
+        get_secs("timeout_secs", client::DEFAULT_TIMEOUT),
+    )
+}
+
+/// Returns the context window of a model in tokens.
+///
+/// Keys of the `[ai.context_windows]` table are model prefixes or globs and win over the built-in
+/// table; the longest matching key wins.
 ///
 /// # Arguments
 ///
-/// * `abort` - The error message.
-/// * `auto_add` - Whether auto-add is enabled.
-pub fn ai_generating_error(abort: &str, auto_add: bool) {
-    eprintln!("{}", abort);
-    if auto_add {
-        output::print_verbose("No staged changes to commit.");
+/// * `model_name` - The name of the AI model.
+///
+/// # Returns
+///
+/// * `Some(tokens)` with the configured or built-in window, `None` for unknown models.
+pub fn get_context_window(model_name: &str) -> Option<u64> {
+    let configured = get_ai_config().ok().and_then(|ai| {
+        ai.get(CONTEXT_WINDOWS_KEY)
+            .and_then(|v| v.as_table())
+            .and_then(|windows| {
+                windows
+                    .iter()
+                    .filter(|(pattern, _)| matches_pattern(pattern, model_name))
+                    .max_by_key(|(pattern, _)| pattern.len())
+                    .and_then(|(_, window)| window.as_integer())
+                    .and_then(|window| u64::try_from(window).ok())
+            })
+    });
+    configured.or_else(|| tokens::context_window(model_name))
+}
+
+/// Returns the number of tokens kept free for the answer.
+///
+/// # Returns
+///
+/// * The `response_reserve` value of the ai section, with a default if not configured.
+pub fn get_response_reserve() -> u64 {
+    get_ai_config()
+        .ok()
+        .and_then(|ai| ai.get("response_reserve").and_then(|v| v.as_integer()))
+        .and_then(|reserve| u64::try_from(reserve).ok())
+        .unwrap_or(tokens::DEFAULT_RESPONSE_RESERVE)
+}
+
+/// Returns the retry policy for AI requests from the ai section.
+///
+/// # Returns
+///
+/// * `RetryPolicy` built from `retry_max_attempts`, `retry_base_delay_ms`, `retry_max_delay_ms`
+///   and `retry_jitter`, with defaults for missing keys.
+pub fn get_retry_policy() -> RetryPolicy {
+    let mut policy = RetryPolicy::default();
+    let Ok(ai) = get_ai_config() else {
+        return policy;
+    };
+    let get_u64 = |key: &str| {
+        ai.get(key)
+            .and_then(|v| v.as_integer())
+            .and_then(|v| u64::try_from(v).ok())
+    };
+    if let Some(max_attempts) = get_u64("retry_max_attempts") {
+        policy.max_attempts = max_attempts.clamp(1, u32::MAX as u64) as u32;
+    }
+    if let Some(base_delay) = get_u64("retry_base_delay_ms") {
+        policy.base_delay = Duration::from_millis(base_delay);
+    }
+    if let Some(max_delay) = get_u64("retry_max_delay_ms") {
+        policy.max_delay = Duration::from_millis(max_delay);
+    }
+    if let Some(jitter) = ai.get("retry_jitter").and_then(|v| v.as_bool()) {
+        policy.jitter = jitter;
+    }
+    output::print_verbose(&format!("Using ai retry policy: {:?}", policy));
+    policy
+}
+
+/// Builds the provider registry: `[[providers]]` entries of the config file first,
+/// then the built-in providers.
+///
+/// # Returns
+///
+/// * `Registry` with the effective lookup order. Invalid entries are skipped with a warning.
+pub fn get_provider_registry() -> Registry {
+    let mut registry = Registry::default();
+    let providers = gim_config::config::get_config()
+        .ok()
+        .and_then(|config| config.get(PROVIDERS_SECTION).cloned());
+    if let Some(providers) = providers {
+        match providers {
+            toml::Value::Array(entries) => {
+                for entry in entries {
+                    match entry.try_into::<ProviderConfig>() {
+                        Ok(config) => {
+                            output::print_verbose(&format!(
+                                "Register custom provider '{}' for models '{}'",
+                                config.name, config.model
+                            ));
+                            registry.register(Arc::new(OpenAiCompatible::from_config(config)));
+                        }
+                        Err(e) => output::print_warning(&format!(
+                            "Ignore invalid [[{}]] entry: {}",
+                            PROVIDERS_SECTION, e
+                        )),
+                    }
+                }
+            }
+            _ => output::print_warning(&format!(
+                "Ignore '{}' in config: expected [[{}]] tables",
+                PROVIDERS_SECTION, PROVIDERS_SECTION
+            )),
+        }
+    }
+    registry.register_builtin();
+    registry
+}
+
+/// Prints the effective provider registry in lookup order.
+pub fn print_providers() {
+    let registry = get_provider_registry();
+    output::print_normal(&format!(
+        "{:<20} {:<16} {:<30} URL",
+        "Name", "Models", "Auth"
+    ));
+    for provider in registry.providers() {
+        let auth = provider
+            .auth_headers("")
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>()
+            .join(", ");
+        output::print_normal(&format!(
+            "{:<20} {:<16} {:<30} {}",
+            provider.name(),
+            provider.model_pattern().unwrap_or_else(|| "-".to_string()),
+            if auth.is_empty() {
+                "-".to_string()
+            } else {
+                auth
+            },
+            provider.default_url().unwrap_or_else(|| "-".to_string())
+        ));
+        report::push(
+            "providers",
+            serde_json::json!({
+                "name": provider.name(),
+                "models": provider.model_pattern(),
+                "auth": provider
+                    .auth_headers("")
+                    .into_iter()
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>(),
+                "url": provider.default_url(),
+            }),
+        );
     }
+    output::print_normal(
+        "\nProviders are consulted top-down; entries from the [[providers]] section of the config file come first.",
+    );
 }
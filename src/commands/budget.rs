@@ -0,0 +1,207 @@
+use chrono::{Datelike, Local};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::commands::stats;
+use crate::core::ai::client::Endpoint;
+use crate::core::ai::error::AiError;
+use crate::core::ai::types::Usage;
+use crate::core::{git, ledger};
+use crate::utils::output;
+
+static BUDGET_SECTION: &str = "budget";
+
+lazy_static! {
+    static ref IGNORED: AtomicBool = AtomicBool::new(false);
+    /// Estimated usage of the requests that passed the check but are not in the ledger yet.
+    static ref IN_FLIGHT: Mutex<Spent> = Mutex::new(Spent::default());
+}
+
+/// Caps on the usage recorded in the ledger; unset caps are not checked.
+#[derive(Deserialize, Debug, Default)]
+pub struct Limits {
+    pub daily_tokens: Option<u64>,
+    pub monthly_tokens: Option<u64>,
+    /// Spend per day in the currency of the `[pricing]` section.
+    pub daily_cost: Option<f64>,
+    /// Spend per month in the currency of the `[pricing]` section.
+    pub monthly_cost: Option<f64>,
+}
+
+/// The `[budget]` section of the config file.
+#[derive(Deserialize, Debug, Default)]
+pub struct BudgetConfig {
+    /// Exceeding these caps prints a warning.
+    #[serde(default)]
+    pub soft: Limits,
+    /// Exceeding these caps refuses the request.
+    #[serde(default)]
+    pub hard: Limits,
+    /// Whether only the usage of the current repository counts.
+    #[serde(default)]
+    pub per_repo: bool,
+}
+
+/// Sets whether hard budgets are ignored for this run, e.g. in an emergency.
+///
+/// # Arguments
+///
+/// * `ignored` - If true, exceeded hard caps only print a warning.
+pub fn set_ignored(ignored: bool) {
+    IGNORED.store(ignored, Ordering::Relaxed);
+}
+
+/// Returns the `[budget]` section of the config file.
+///
+/// # Returns
+///
+/// * `Ok(None)` if no budget is configured.
+/// * `Err(AiError::ConfigInvalid)` if the section cannot be read.
+pub fn get_budget_config() -> Result<Option<BudgetConfig>, AiError> {
+    let Some(section) = gim_config::config::get_config()
+        .ok()
+        .and_then(|config| config.get(BUDGET_SECTION).cloned())
+    else {
+        return Ok(None);
+    };
+    section
+        .try_into()
+        .map(Some)
+        .map_err(|e| AiError::ConfigInvalid(format!("[{}] ({})", BUDGET_SECTION, e)))
+}
+
+/// Usage spent within a period.
+#[derive(Default)]
+struct Spent {
+    tokens: u64,
+    cost: f64,
+}
+
+/// The estimate of a request counted against the budgets until it is dropped.
+///
+/// Keep it until the usage of the request is recorded in the ledger, so requests sent
+/// concurrently cannot each pass a cap the others are about to use up.
+#[must_use]
+pub struct Reservation {
+    tokens: u64,
+    cost: f64,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        let mut in_flight = IN_FLIGHT.lock().unwrap_or_else(|e| e.into_inner());
+        in_flight.tokens = in_flight.tokens.saturating_sub(self.tokens);
+        in_flight.cost = (in_flight.cost - self.cost).max(0.0);
+    }
+}
+
+/// Checks whether a request would exceed a budget, before it is sent.
+///
+/// Requests still in flight count with their estimates, and the estimate of this request is
+/// reserved until the returned [`Reservation`] is dropped.
+///
+/// # Arguments
+///
+/// * `endpoint` - The endpoint about to be asked; its model determines the price.
+/// * `estimate` - The estimated token counts of the request.
+///
+/// # Returns
+///
+/// * `Ok(Reservation)` if the request fits, possibly after warning about a soft cap.
+/// * `Err(AiError::BudgetExceeded)` if it would exceed a hard cap and budgets are not ignored.
+pub fn check_budget(endpoint: &Endpoint, estimate: &Usage) -> Result<Reservation, AiError> {
+    let Some(config) = get_budget_config()? else {
+        return Ok(Reservation {
+            tokens: 0,
+            cost: 0.0,
+        });
+    };
+    // Checked and reserved at once, concurrent requests wait for each other here
+    let mut in_flight = IN_FLIGHT.lock().unwrap_or_else(|e| e.into_inner());
+    let repo = if config.per_repo {
+        git::get_git_root().map(|root| root.display().to_string())
+    } else {
+        None
+    };
+
+    let now = Local::now();
+    let mut today = Spent {
+        tokens: in_flight.tokens,
+        cost: in_flight.cost,
+    };
+    let mut month = Spent {
+        tokens: in_flight.tokens,
+        cost: in_flight.cost,
+    };
+    let entries = ledger::read_entries().unwrap_or_else(|e| {
+        output::print_warning(&format!("Failed to read the usage ledger: {}", e));
+        Vec::new()
+    });
+    for entry in entries
+        .iter()
+        .filter(|entry| repo.as_ref().is_none_or(|repo| &entry.repo == repo))
+    {
+        if entry.time.year() != now.year() || entry.time.month() != now.month() {
+            continue;
+        }
+        let tokens = entry.usage().total();
+        let cost = entry.cost.unwrap_or_default();
+        month.tokens += tokens;
+        month.cost += cost;
+        if entry.time.day() == now.day() {
+            today.tokens += tokens;
+            today.cost += cost;
+        }
+    }
+
+    let tokens = estimate.total();
+    let cost = stats::estimate_cost(&endpoint.model, estimate).unwrap_or_default();
+    let currency = stats::get_currency();
+    let checks = |limits: &Limits| {
+        let mut exceeded = Vec::new();
+        let mut check_tokens = |name: &str, limit: Option<u64>, spent: u64| {
+            if let Some(limit) = limit
+                && spent + tokens > limit
+            {
+                exceeded.push(format!(
+                    "{} token budget of {} ({} used + about {} for this request)",
+                    name, limit, spent, tokens
+                ));
+            }
+        };
+        check_tokens("daily", limits.daily_tokens, today.tokens);
+        check_tokens("monthly", limits.monthly_tokens, month.tokens);
+        let mut check_cost = |name: &str, limit: Option<f64>, spent: f64| {
+            if let Some(limit) = limit
+                && spent + cost > limit
+            {
+                exceeded.push(format!(
+                    "{} spending budget of {:.2} {} ({:.4} spent + about {:.4} for this request)",
+                    name, limit, currency, spent, cost
+                ));
+            }
+        };
+        check_cost("daily", limits.daily_cost, today.cost);
+        check_cost("monthly", limits.monthly_cost, month.cost);
+        exceeded
+    };
+
+    let hard = checks(&config.hard);
+    if !hard.is_empty() {
+        if !IGNORED.load(Ordering::Relaxed) {
+            return Err(AiError::BudgetExceeded(hard.join("; ")));
+        }
+        output::print_warning(&format!(
+            "Ignoring the exceeded {} as requested",
+            hard.join("; ")
+        ));
+    }
+    for soft in checks(&config.soft) {
+        output::print_warning(&format!("Over the soft {}", soft));
+    }
+    in_flight.tokens += tokens;
+    in_flight.cost += cost;
+    Ok(Reservation { tokens, cost })
+}
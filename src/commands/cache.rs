@@ -0,0 +1,127 @@
+use chrono::Duration;
+use lazy_static::lazy_static;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::core::ai::client::Endpoint;
+use crate::core::cache;
+use crate::utils::{output, report};
+
+static CACHE_SECTION: &str = "cache";
+static DEFAULT_TTL_HOURS: i64 = 24;
+static DEFAULT_MAX_SIZE_MB: u64 = 16;
+
+lazy_static! {
+    static ref DISABLED: AtomicBool = AtomicBool::new(false);
+}
+
+/// Sets whether the response cache is bypassed for this run.
+///
+/// # Arguments
+///
+/// * `disabled` - If true, answers are neither read from nor written to the cache.
+pub fn set_disabled(disabled: bool) {
+    DISABLED.store(disabled, Ordering::Relaxed);
+}
+
+/// Returns a value of the `[cache]` section of the config file.
+fn get_cache_value(key: &str) -> Option<toml::Value> {
+    gim_config::config::get_config_value(CACHE_SECTION, key).ok()
+}
+
+/// Returns whether answers are cached in this run.
+///
+/// # Returns
+///
+/// * `false` if `--no-cache` is given or `enabled = false` is configured, `true` otherwise.
+pub fn get_cache_enabled() -> bool {
+    !DISABLED.load(Ordering::Relaxed)
+        && get_cache_value("enabled")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true)
+}
+
+/// Returns how long cached answers stay valid.
+///
+/// # Returns
+///
+/// * The `ttl_hours` value of the cache section, 24 hours if not configured.
+pub fn get_cache_ttl() -> Duration {
+    Duration::hours(
+        get_cache_value("ttl_hours")
+            .and_then(|v| v.as_integer())
+            .unwrap_or(DEFAULT_TTL_HOURS),
+    )
+}
+
+/// Returns the size limit of the cache in bytes.
+///
+/// # Returns
+///
+/// * The `max_size_mb` value of the cache section, 16 MB if not configured.
+pub fn get_cache_max_size() -> u64 {
+    get_cache_value("max_size_mb")
+        .and_then(|v| v.as_integer())
+        .and_then(|mb| u64::try_from(mb).ok())
+        .unwrap_or(DEFAULT_MAX_SIZE_MB)
+        * 1024
+        * 1024
+}
+
+/// Looks up the answer of an endpoint to a prompt sent before.
+///
+/// # Arguments
+///
+/// * `endpoint` - The endpoint about to be asked.
+/// * `system` - The system prompt.
+/// * `user` - The user content.
+///
+/// # Returns
+///
+/// * `Some(answer)` on a cache hit, `None` on a miss or if the cache is disabled.
+pub fn lookup(endpoint: &Endpoint, system: &str, user: &str) -> Option<String> {
+    if !get_cache_enabled() {
+        return None;
+    }
+    let text = cache::get(
+        &cache::key(&endpoint.url, &endpoint.model, system, user),
+        get_cache_ttl(),
+    )?;
+    output::print_verbose(&format!(
+        "Using the cached answer of model '{}'",
+        endpoint.model
+    ));
+    Some(text)
+}
+
+/// Caches the answer of an endpoint to a prompt, unless the cache is disabled.
+///
+/// # Arguments
+///
+/// * `endpoint` - The endpoint that answered.
+/// * `system` - The system prompt.
+/// * `user` - The user content.
+/// * `text` - The answer.
+pub fn store(endpoint: &Endpoint, system: &str, user: &str, text: &str) {
+    if !get_cache_enabled() {
+        return;
+    }
+    let key = cache::key(&endpoint.url, &endpoint.model, system, user);
+    if let Err(e) = cache::put(&key, &endpoint.model, text, get_cache_max_size()) {
+        output::print_warning(&format!("Failed to cache the ai answer: {}", e));
+    }
+}
+
+/// Removes every cached answer and prints how much was removed.
+pub fn clear_cache() -> std::io::Result<()> {
+    let (count, bytes) = cache::clear()?;
+    output::print_normal(&format!(
+        "Removed {} cached answers ({} KB)",
+        count,
+        bytes.div_ceil(1024)
+    ));
+    report::set(
+        "cache",
+        serde_json::json!({ "removed": count, "bytes": bytes }),
+    );
+    Ok(())
+}
@@ -1,16 +1,23 @@
-use crate::commands::{ai, prompt};
-use crate::core::ai::client;
-use crate::utils::output;
-use indoc::{eprintdoc, printdoc};
+use crate::commands::{ai, budget, cache, config, prompt, stats};
+use crate::config::constants::DIFF_SIZE_LIMIT;
+use crate::core::ai::client::{self, Completion, Endpoint};
+use crate::core::ai::error::AiError;
+use crate::core::ai::tokens;
+use crate::core::ai::types::{Message, Usage};
+use crate::core::message::{self, CommitMessage};
+use crate::core::{diff, git};
+use crate::utils::{input, output, report};
+use indoc::formatdoc;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 
 /// Generates commit message from diff content.
 ///
 /// # Arguments
 ///
 /// * `diff_content` - The diff content to generate message from.
-/// * `url` - The AI API URL.
-/// * `model_name` - The AI model name.
-/// * `api_key` - The API key.
+/// * `endpoints` - The resolved AI endpoints, primary first; later ones are tried when earlier ones fail.
 /// * `language` - The language for the commit message.
 /// * `verbose` - Whether to print verbose output.
 /// * `custom_title` - Optional custom title for the commit.
@@ -19,67 +26,148 @@ use indoc::{eprintdoc, printdoc};
 ///
 /// # Returns
 ///
-/// * `Ok((subject, message))` containing the commit subject and message, or `Err` if generation fails.
+/// * `Ok((subject, message, file_changes))` containing the commit subject, the message and the
+///   summaries of the changed files the subject was generated from, or `Err(AiError)` if
+///   generation fails.
 pub async fn generate_commit_message(
     mut diff_content: String,
-    url: String,
-    model_name: String,
-    api_key: String,
+    endpoints: &[Endpoint],
     language: String,
     verbose: bool,
     custom_title: Option<String>,
     custom_diff_prompt: Option<String>,
     custom_subject_prompt: Option<String>,
-) -> Result<(String, String), Box<dyn std::error::Error>> {
-    if language != "English" {
-        diff_content.push_str(&format!(
+) -> Result<(String, String, String), AiError> {
+    let language_note = if language != "English" {
+        format!(
             "\n The answer should be in {} language. If you cannot recognize this language, use English instead.",
             language
-        ));
+        )
+    } else {
+        String::new()
+    };
+
+    if custom_title.is_none()
+        && ai::get_single_request_enabled()
+        && ai::get_candidates() <= 1
+        && let Some((subject, message, used)) = generate_in_one_request(
+            &diff_content,
+            endpoints,
+            &language_note,
+            verbose,
+            custom_diff_prompt.as_deref(),
+            custom_subject_prompt.as_deref(),
+        )
+        .await?
+    {
+        print_generated(endpoints, used, &diff_content, &subject, &message);
+        // The body of the single answer holds the file summaries
+        return Ok((subject, message.clone(), message));
     }
 
+    let stream = ai::get_stream_enabled();
     let system = prompt::get_diff_prompt(custom_diff_prompt.as_deref());
-    let res = client::chat(
-        url.clone(),
-        model_name.clone(),
-        api_key.clone(),
-        Some(system),
-        diff_content.clone(),
-        verbose,
-    )
-    .await;
-
-    let file_changes = match res {
-        Ok(msg) => msg,
-        Err(e) => {
-            ai::ai_generating_error(&format!("Error: {}", e), true);
-            return Err(e);
-        }
-    };
-
-    let commit_subject = if let Some(title) = custom_title {
-        title
-    } else {
-        let system = prompt::get_subject_prompt(custom_subject_prompt.as_deref());
-        let res = client::chat(
-            url,
-            model_name,
-            api_key,
-            Some(system),
-            format!("The changes are: \n{}", file_changes),
+    report::set_field("prompts", "diff", &system);
+    // Every part must fit every endpoint, as any of them may end up answering
+    let budget = request_budget(&diff_content, endpoints, &system);
+    let fits = budget.is_none_or(|budget| tokens::estimate(&diff_content) <= budget);
+    let per_file = ai::get_per_file_enabled() && diff::count_files(&diff_content) > 1;
+    let (file_changes, mut used) = if fits && !per_file {
+        diff_content.push_str(&language_note);
+        ask(
+            endpoints,
+            system,
+            diff_content.clone(),
             verbose,
+            stream.then_some("Summarizing changes"),
+            false,
         )
-        .await;
+        .await?
+    } else {
+        let budget = budget
+            .unwrap_or(u64::MAX)
+            .saturating_sub(PART_NOTE_TOKENS)
+            .max(1);
+        let parts = if per_file {
+            diff::split_per_file(&diff_content, budget)
+        } else {
+            diff::split_diff(&diff_content, budget)
+        };
+        summarize_in_parts(parts, endpoints, system, &language_note, verbose).await?
+    };
 
-        match res {
-            Ok(answer) => answer,
-            Err(e) => format!("Error: {}", e),
+    let (commit_subject, commit_message) = match custom_title {
+        Some(title) => (title, file_changes.clone()),
+        None => {
+            // Start from the endpoint that answered, the ones before it just failed
+            let (subject, message, index) = choose_subject(
+                &endpoints[used..],
+                file_changes.clone(),
+                verbose,
+                custom_subject_prompt.as_deref(),
+                stream,
+            )
+            .await?;
+            used += index;
+            (subject, message)
         }
     };
 
+    print_generated(
+        endpoints,
+        used,
+        &diff_content,
+        &commit_subject,
+        &commit_message,
+    );
+    Ok((commit_subject, commit_message, file_changes))
+}
+
+/// Prints the generated commit message and, in verbose mode, the model that generated it.
+///
+/// # Arguments
+///
+/// * `endpoints` - The endpoints that were asked.
+/// * `used` - The index of the endpoint that answered last.
+/// * `diff_content` - The diff content sent to the AI.
+/// * `commit_subject` - The commit subject.
+/// * `commit_message` - The commit message body.
+fn print_generated(
+    endpoints: &[Endpoint],
+    used: usize,
+    diff_content: &str,
+    commit_subject: &str,
+    commit_message: &str,
+) {
+    let endpoint = &endpoints[used];
+    output::print_verbose(&format!(
+        "Commit message generated by model '{}' ({}){}",
+        endpoint.model,
+        endpoint.provider.name(),
+        if used > 0 { " as fallback" } else { "" }
+    ));
+    report::set(
+        "model",
+        serde_json::json!({
+            "provider": endpoint.provider.name(),
+            "name": endpoint.model,
+            "fallback": used > 0,
+        }),
+    );
+
     output::print_verbose(&format!("AI chat content: {}", diff_content));
+    print_commit_message(commit_subject, commit_message);
+}
+
+/// Prints a commit subject and message between markers.
+///
+/// # Arguments
+///
+/// * `commit_subject` - The commit subject.
+/// * `commit_message` - The commit message body.
+pub fn print_commit_message(commit_subject: &str, commit_message: &str) {
     output::print_normal("");
-    printdoc!(
+    output::print_block(&formatdoc!(
         r#"
         >>>>>>>>>>>>>>>>>>>>>>>>>
         Commit subject: "{}"
@@ -88,10 +176,255 @@ pub async fn generate_commit_message(
         <<<<<<<<<<<<<<<<<<<<<<<<<
         "#,
         commit_subject,
-        file_changes
+        commit_message
+    ));
+}
+
+/// Asks for the summary of the changes and the subject in a single JSON answer.
+///
+/// # Arguments
+///
+/// * `diff_content` - The diff content to generate the message from.
+/// * `endpoints` - The endpoints to try, in order.
+/// * `language_note` - Text appended to the changes, asking for the answer language.
+/// * `verbose` - Whether to print verbose output.
+/// * `custom_diff_prompt` - Optional custom diff prompt.
+/// * `custom_subject_prompt` - Optional custom subject prompt.
+///
+/// # Returns
+///
+/// * `Ok(Some((subject, message, index)))` with the index of the endpoint that answered.
+/// * `Ok(None)` if the changes do not fit into one request or the answer cannot be parsed; the
+///   caller then falls back to the two-request pipeline.
+/// * `Err` if every endpoint fails.
+async fn generate_in_one_request(
+    diff_content: &str,
+    endpoints: &[Endpoint],
+    language_note: &str,
+    verbose: bool,
+    custom_diff_prompt: Option<&str>,
+    custom_subject_prompt: Option<&str>,
+) -> Result<Option<(String, String, usize)>, AiError> {
+    let system = format!(
+        "{}\n{}{}",
+        prompt::get_diff_prompt(custom_diff_prompt),
+        prompt::get_subject_prompt(custom_subject_prompt),
+        CommitMessage::single_request_instruction()
     );
+    report::set_field("prompts", "single_request", &system);
+    let budget = request_budget(diff_content, endpoints, &system);
+    if budget.is_some_and(|budget| tokens::estimate(diff_content) > budget) {
+        output::print_verbose("The changes are too large for a single request, using two requests");
+        return Ok(None);
+    }
 
-    Ok((commit_subject, file_changes))
+    let (answer, used) = ask(
+        endpoints,
+        system,
+        format!("{}{}", diff_content, language_note),
+        verbose,
+        None,
+        true,
+    )
+    .await?;
+    match CommitMessage::parse(&answer).filter(|parsed| !parsed.body.trim().is_empty()) {
+        Some(parsed) => {
+            output::print_verbose(&format!("Parsed commit message: {:?}", parsed));
+            Ok(Some((
+                parsed.header(),
+                parsed.body.trim().to_string(),
+                used,
+            )))
+        }
+        None => {
+            output::print_warning("Failed to parse the single answer, using two requests instead");
+            Ok(None)
+        }
+    }
+}
+
+/// Completion tokens reserved for an answer when checking budgets before a request.
+const ESTIMATED_COMPLETION_TOKENS: u64 = 512;
+
+/// Sends one prompt to the endpoints in turn until one of them answers.
+///
+/// Budgets are checked before each request; an exceeded hard budget stops without trying
+/// further endpoints.
+///
+/// # Arguments
+///
+/// * `endpoints` - The endpoints to try, in order.
+/// * `system` - The system prompt.
+/// * `user` - The user content.
+/// * `verbose` - Whether to print verbose output.
+/// * `stream_label` - If set, streams the answer to the terminal under this label.
+/// * `json` - Whether to ask for a commit message matching the JSON schema of [`CommitMessage`].
+///
+/// # Returns
+///
+/// * `Ok((answer, index))` with the index of the endpoint that answered.
+/// * `Err` with the error of the last endpoint if all of them fail.
+async fn ask(
+    endpoints: &[Endpoint],
+    system: String,
+    user: String,
+    verbose: bool,
+    stream_label: Option<&str>,
+    json: bool,
+) -> Result<(String, usize), AiError> {
+    let turns = vec![Message {
+        role: "user".to_string(),
+        content: user,
+    }];
+    ask_turns(endpoints, system, turns, verbose, stream_label, json).await
+}
+
+/// Sends a conversation to the endpoints in turn until one of them answers, like [`ask`].
+///
+/// # Arguments
+///
+/// * `endpoints` - The endpoints to try, in order.
+/// * `system` - The system prompt.
+/// * `turns` - The turns of the conversation, ending with the user input.
+/// * `verbose` - Whether to print verbose output.
+/// * `stream_label` - If set, streams the answer to the terminal under this label.
+/// * `json` - Whether to ask for a commit message matching the JSON schema of [`CommitMessage`].
+///
+/// # Returns
+///
+/// * `Ok((answer, index))` with the index of the endpoint that answered.
+/// * `Err` with the error of the last endpoint if all of them fail.
+async fn ask_turns(
+    endpoints: &[Endpoint],
+    system: String,
+    turns: Vec<Message>,
+    verbose: bool,
+    stream_label: Option<&str>,
+    json: bool,
+) -> Result<(String, usize), AiError> {
+    let user = conversation_text(&turns);
+    let mut last_error = AiError::ConfigMissing("no ai endpoint configured".to_string());
+    let estimate = Usage {
+        prompt_tokens: tokens::estimate(&system) + tokens::estimate(&user),
+        completion_tokens: ESTIMATED_COMPLETION_TOKENS,
+    };
+    for (index, endpoint) in endpoints.iter().enumerate() {
+        if let Some(text) = cache::lookup(endpoint, &system, &user) {
+            if let Some(label) = stream_label {
+                output::print_normal(&format!("{}:\n{}", label, text));
+            }
+            report_request(endpoint, None, true);
+            return Ok((text, index));
+        }
+        let _reservation = budget::check_budget(endpoint, &estimate)?;
+        match ask_endpoint(
+            endpoint,
+            system.clone(),
+            &turns,
+            verbose,
+            stream_label,
+            json,
+        )
+        .await
+        {
+            Ok(completion) => {
+                stats::record_usage(endpoint, completion.usage, &estimate);
+                report_request(endpoint, completion.usage, false);
+                cache::store(endpoint, &system, &user, &completion.text);
+                return Ok((completion.text, index));
+            }
+            Err(e) => {
+                if let Some(next) = endpoints.get(index + 1) {
+                    output::print_warning(&format!(
+                        "Model '{}' failed: {}; falling back to '{}'",
+                        endpoint.model, e, next.model
+                    ));
+                }
+                last_error = e;
+            }
+        }
+    }
+    Err(last_error)
+}
+
+/// Returns the text keying the cache for a conversation; a single user turn is keyed by its content.
+fn conversation_text(turns: &[Message]) -> String {
+    match turns {
+        [turn] => turn.content.clone(),
+        _ => turns
+            .iter()
+            .map(|turn| format!("{}: {}", turn.role, turn.content))
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+    }
+}
+
+/// Records an answered request in the JSON report.
+///
+/// # Arguments
+///
+/// * `endpoint` - The endpoint that answered.
+/// * `usage` - The token counts, `None` if not reported or the answer was cached.
+/// * `cached` - Whether the answer came from the response cache.
+fn report_request(endpoint: &Endpoint, usage: Option<Usage>, cached: bool) {
+    if !report::is_enabled() {
+        return;
+    }
+    report::push(
+        "requests",
+        serde_json::json!({
+            "provider": endpoint.provider.name(),
+            "model": endpoint.model,
+            "cached": cached,
+            "prompt_tokens": usage.map(|usage| usage.prompt_tokens),
+            "completion_tokens": usage.map(|usage| usage.completion_tokens),
+            "cost": usage.and_then(|usage| stats::estimate_cost(&endpoint.model, &usage)),
+        }),
+    );
+}
+
+/// Sends one prompt to the AI, rendering the answer live when a stream label is given.
+///
+/// # Arguments
+///
+/// * `endpoint` - The resolved AI endpoint.
+/// * `system` - The system prompt.
+/// * `turns` - The turns of the conversation, ending with the user input.
+/// * `verbose` - Whether to print verbose output.
+/// * `stream_label` - If set, streams the answer to the terminal under this label.
+/// * `json` - Whether to ask for a commit message in JSON; such answers are never streamed.
+///
+/// # Returns
+///
+/// * `Ok(Completion)` containing the full answer, or `Err` if the request fails.
+async fn ask_endpoint(
+    endpoint: &Endpoint,
+    system: String,
+    turns: &[Message],
+    verbose: bool,
+    stream_label: Option<&str>,
+    json: bool,
+) -> Result<Completion, AiError> {
+    if json {
+        let schema = CommitMessage::schema();
+        return client::chat_json(
+            endpoint,
+            Some(system),
+            turns,
+            verbose,
+            message::SCHEMA_NAME,
+            &schema,
+        )
+        .await;
+    }
+    let Some(label) = stream_label else {
+        return client::chat(endpoint, Some(system), turns, verbose).await;
+    };
+    output::print_normal(&format!("{}:", label));
+    let res =
+        client::chat_stream(endpoint, Some(system), turns, verbose, output::print_stream).await;
+    output::print_normal("");
+    res
 }
 
 /// Commits the generated message to git.
@@ -101,40 +434,615 @@ pub async fn generate_commit_message(
 /// * `subject` - The commit subject.
 /// * `message` - The commit message body.
 /// * `overwrite` - If true, amends the last commit.
-pub fn execute_commit(subject: &str, message: &str, overwrite: bool) {
-    if crate::core::git::git_commit(subject, message, overwrite) {
-        output::print_normal(
-            "✅ Successfully committed changes! If you were discontent with the commit message and want to polish or revise it, run 'gim -p' or 'git commit --amend'",
-        );
-    } else {
-        eprintln!("Error: Failed to commit changes");
+///
+/// # Returns
+///
+/// * `Ok(())` if the commit was created, `Err` if git failed.
+pub fn execute_commit(
+    subject: &str,
+    message: &str,
+    overwrite: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !git::git_commit(subject, message, overwrite) {
+        return Err("Failed to commit changes".into());
     }
+    output::print_normal(
+        "✅ Successfully committed changes! If you were discontent with the commit message and want to polish or revise it, run 'gim -p' or 'git commit --amend'",
+    );
+    report::set(
+        "commit",
+        serde_json::json!({ "sha": git::get_head_sha(), "amend": overwrite }),
+    );
+    Ok(())
 }
 
-/// Checks if diff content exceeds the line limit.
+/// Keeps the endpoints whose context window the changes fit into.
+///
+/// The diff budget of a model is its context window minus the system prompt and the tokens
+/// reserved for the answer. If the changes fit no model, all endpoints are kept and the changes
+/// are summarized in parts. Models without a known window are never skipped.
 ///
 /// # Arguments
 ///
 /// * `diff_content` - The diff content to check.
-/// * `diff_limit` - The maximum allowed lines.
+/// * `endpoints` - The endpoints that may be asked, primary first.
+/// * `custom_diff_prompt` - Optional custom diff prompt.
 ///
 /// # Returns
 ///
-/// * `Ok(())` if within limit, exits with error if exceeds.
-pub fn check_diff_limit(
+/// * The endpoints with the ones that are too small removed.
+pub fn fit_context_budget(
     diff_content: &str,
-    diff_limit: usize,
-) -> Result<(), Box<dyn std::error::Error>> {
-    if diff_content.lines().count() > diff_limit {
-        eprintdoc!(
-            r"
-            Your changed lines count ({}) exceeds the limit: {}.
-            Please use 'git commit' to commit the changes or adjust the limit by 'gim config --change-limit <LIMIT>' and try again.
-            ",
-            diff_content.lines().count(),
-            diff_limit
-        );
-        std::process::exit(1);
+    endpoints: Vec<Endpoint>,
+    custom_diff_prompt: Option<&str>,
+) -> Vec<Endpoint> {
+    let diff_tokens = tokens::estimate(diff_content);
+    let system = prompt::get_diff_prompt(custom_diff_prompt);
+    let (fitting, skipped): (Vec<Endpoint>, Vec<Endpoint>) =
+        endpoints.into_iter().partition(|endpoint| {
+            context_budget(endpoint, &system).is_none_or(|budget| diff_tokens <= budget)
+        });
+    if fitting.is_empty() {
+        output::print_normal(&format!(
+            "The changes (about {} tokens) exceed the context window of every model, summarizing them in parts",
+            diff_tokens
+        ));
+        return skipped;
+    }
+    for model in skipped.into_iter().map(|endpoint| endpoint.model) {
+        output::print_warning(&format!(
+            "Skip model '{}', the changes exceed its context window",
+            model
+        ));
+    }
+    fitting
+}
+
+/// Returns how many tokens of changes fit into one request to every endpoint.
+///
+/// Besides the context windows, the line limit bounds the changes sent at once: the one set by
+/// `gim config --lines-limit`, else the default limit if a model has no known window. Changes
+/// over the limit are summarized in parts of about that many lines.
+///
+/// # Arguments
+///
+/// * `diff_content` - The diff content to send.
+/// * `endpoints` - The endpoints that may be asked.
+/// * `system` - The system prompt sent along with the changes.
+///
+/// # Returns
+///
+/// * `Some(tokens)` with the smallest budget, `None` if nothing bounds the changes.
+fn request_budget(diff_content: &str, endpoints: &[Endpoint], system: &str) -> Option<u64> {
+    let window_budget = endpoints
+        .iter()
+        .filter_map(|endpoint| context_budget(endpoint, system))
+        .min();
+    let lines_limit = config::get_lines_limit().or_else(|| {
+        endpoints
+            .iter()
+            .any(|endpoint| ai::get_context_window(&endpoint.model).is_none())
+            .then_some(DIFF_SIZE_LIMIT)
+    });
+    let lines = diff_content.lines().count();
+    let lines_budget = lines_limit.filter(|&limit| lines > limit).map(|limit| {
+        output::print_verbose(&format!(
+            "The changes ({} lines) exceed the line limit of {}, sending them in parts",
+            lines, limit
+        ));
+        // The tokens of as many lines as the limit allows, on average
+        (tokens::estimate(diff_content) * limit as u64 / lines as u64).max(1)
+    });
+    window_budget.into_iter().chain(lines_budget).min()
+}
+
+/// Returns how many tokens of changes fit into one request to the endpoint.
+///
+/// # Arguments
+///
+/// * `endpoint` - The endpoint to ask.
+/// * `system` - The system prompt sent along with the changes.
+///
+/// # Returns
+///
+/// * `Some(tokens)` with the context window of the model minus the system prompt and the answer
+///   reserve, `None` if the window of the model is unknown.
+fn context_budget(endpoint: &Endpoint, system: &str) -> Option<u64> {
+    let Some(window) = ai::get_context_window(&endpoint.model) else {
+        output::print_verbose(&format!(
+            "Context window of model '{}' is unknown, the changes are bounded by the line limit",
+            endpoint.model
+        ));
+        return None;
+    };
+    let prompt_tokens = tokens::estimate(system);
+    let reserve = ai::get_response_reserve();
+    let budget = window.saturating_sub(prompt_tokens + reserve);
+    output::print_verbose(&format!(
+        "Context budget of model '{}': {} tokens ({} window - {} prompt - {} answer reserve)",
+        endpoint.model, budget, window, prompt_tokens, reserve
+    ));
+    Some(budget)
+}
+
+/// Tokens kept free in every part for the note telling the AI which part it is looking at.
+const PART_NOTE_TOKENS: u64 = 64;
+
+/// Summarizes changes part by part.
+///
+/// The parts are summarized concurrently with the diff prompt, at most `[ai] concurrency` at a
+/// time, and their summaries are joined in order. Every part is retried and falls back on its own.
+///
+/// # Arguments
+///
+/// * `parts` - The parts of the diff content, e.g. one per file.
+/// * `endpoints` - The endpoints to try for every part, in order.
+/// * `system` - The diff prompt.
+/// * `language_note` - Text appended to every part, asking for the answer language.
+/// * `verbose` - Whether to print verbose output.
+///
+/// # Returns
+///
+/// * `Ok((summary, index))` with the largest index of the endpoints that answered.
+/// * `Err` with the error of the first part that could not be summarized.
+async fn summarize_in_parts(
+    parts: Vec<String>,
+    endpoints: &[Endpoint],
+    system: String,
+    language_note: &str,
+    verbose: bool,
+) -> Result<(String, usize), AiError> {
+    let count = parts.len();
+    output::print_normal(&format!(
+        "Summarizing the changes in {} parts, {} at a time",
+        count,
+        ai::get_concurrency()
+    ));
+    let users = parts
+        .into_iter()
+        .enumerate()
+        .map(|(index, part)| {
+            format!(
+                "This is part {} of {} of the changes, describe only the files in it.\n{}{}",
+                index + 1,
+                count,
+                part,
+                language_note
+            )
+        })
+        .collect();
+    let (summaries, used) =
+        ask_concurrently(endpoints, system, users, verbose, false, "Summarized part").await?;
+    let summaries: Vec<&str> = summaries.iter().map(|summary| summary.trim()).collect();
+    Ok((summaries.join("\n"), used))
+}
+
+/// Sends several prompts concurrently, at most `[ai] concurrency` at a time.
+///
+/// # Arguments
+///
+/// * `endpoints` - The endpoints to try for every prompt, in order.
+/// * `system` - The system prompt shared by all prompts.
+/// * `users` - The user contents, one request each.
+/// * `verbose` - Whether to print verbose output.
+/// * `json` - Whether to ask for commit messages in JSON.
+/// * `progress` - Printed with the number of every answered prompt, e.g. `Summarized part`.
+///
+/// # Returns
+///
+/// * `Ok((answers, index))` with the answers in the order of the prompts and the largest index of
+///   the endpoints that answered.
+/// * `Err` with the error of the first prompt that could not be answered.
+async fn ask_concurrently(
+    endpoints: &[Endpoint],
+    system: String,
+    users: Vec<String>,
+    verbose: bool,
+    json: bool,
+    progress: &str,
+) -> Result<(Vec<String>, usize), AiError> {
+    let count = users.len();
+    let endpoints = Arc::new(endpoints.to_vec());
+    let permits = Arc::new(Semaphore::new(ai::get_concurrency()));
+    let mut tasks = JoinSet::new();
+    for (index, user) in users.into_iter().enumerate() {
+        let (endpoints, permits, system) = (endpoints.clone(), permits.clone(), system.clone());
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            (
+                index,
+                ask(&endpoints, system, user, verbose, None, json).await,
+            )
+        });
+    }
+
+    let mut answers = vec![String::new(); count];
+    let mut used = 0;
+    while let Some(joined) = tasks.join_next().await {
+        let (index, res) = joined.map_err(|e| AiError::BadResponse(e.to_string()))?;
+        let (answer, endpoint) = res?;
+        output::print_normal(&format!("{} {}/{}", progress, index + 1, count));
+        answers[index] = answer;
+        used = used.max(endpoint);
+    }
+    Ok((answers, used))
+}
+
+/// What the user picked from the candidate subjects.
+enum Choice {
+    Candidate(usize),
+    Regenerate,
+    Manual(String),
+}
+
+/// Generates the commit subject, letting the user pick from several candidates if configured.
+///
+/// # Arguments
+///
+/// * `endpoints` - The endpoints to try, in order.
+/// * `file_changes` - The summaries of the changed files.
+/// * `verbose` - Whether to print verbose output.
+/// * `custom_subject_prompt` - Optional custom subject prompt.
+/// * `stream` - Whether a single subject is streamed to the terminal.
+///
+/// # Returns
+///
+/// * `Ok((subject, message, index))` with the index of the endpoint that answered.
+/// * `Err(AiError::Cancelled)` if the user quits the picker.
+async fn choose_subject(
+    endpoints: &[Endpoint],
+    file_changes: String,
+    verbose: bool,
+    custom_subject_prompt: Option<&str>,
+    stream: bool,
+) -> Result<(String, String, usize), AiError> {
+    let count = ai::get_candidates();
+    let mut round = 0;
+    loop {
+        let (mut candidates, used) = generate_subjects(
+            endpoints,
+            &file_changes,
+            verbose,
+            custom_subject_prompt,
+            stream,
+            round * count..(round + 1) * count,
+        )
+        .await?;
+        if candidates.len() > 1 && !input::is_interactive() {
+            output::print_warning(&format!(
+                "Not a terminal, using the first of {} candidates",
+                candidates.len()
+            ));
+        }
+        if candidates.len() == 1 || !input::is_interactive() {
+            let (subject, message) = candidates.swap_remove(0);
+            return Ok((subject, message, used));
+        }
+        match pick_candidate(&candidates).await? {
+            Choice::Candidate(index) => {
+                let (subject, message) = candidates.swap_remove(index);
+                return Ok((subject, message, used));
+            }
+            Choice::Manual(subject) => return Ok((subject, file_changes, used)),
+            Choice::Regenerate => round += 1,
+        }
+    }
+}
+
+/// Asks for candidate subjects for the summaries of the changed files.
+///
+/// Every candidate but the very first asks for a different wording, so regenerated candidates
+/// differ from the earlier ones and from cached answers.
+///
+/// # Arguments
+///
+/// * `endpoints` - The endpoints to try, in order.
+/// * `file_changes` - The summaries of the changed files.
+/// * `verbose` - Whether to print verbose output.
+/// * `custom_subject_prompt` - Optional custom subject prompt.
+/// * `stream` - Whether a single subject is streamed to the terminal.
+/// * `numbers` - The numbers of the candidates to generate, counted over all rounds.
+///
+/// # Returns
+///
+/// * `Ok((candidates, index))` with `(subject, message)` pairs and the largest index of the
+///   endpoints that answered.
+async fn generate_subjects(
+    endpoints: &[Endpoint],
+    file_changes: &str,
+    verbose: bool,
+    custom_subject_prompt: Option<&str>,
+    stream: bool,
+    numbers: std::ops::Range<usize>,
+) -> Result<(Vec<(String, String)>, usize), AiError> {
+    let structured = ai::get_structured_enabled();
+    let mut system = prompt::get_subject_prompt(custom_subject_prompt);
+    if structured {
+        system.push_str(CommitMessage::instruction());
+    }
+    report::set_field("prompts", "subject", &system);
+    let mut users: Vec<String> = numbers
+        .map(|number| {
+            let mut user = format!("The changes are: \n{}", file_changes);
+            if number > 0 {
+                user.push_str(&format!(
+                    "\nThis is suggestion {}, word it differently from the most obvious one.",
+                    number + 1
+                ));
+            }
+            user
+        })
+        .collect();
+    let (answers, used) = if users.len() == 1 {
+        let (answer, used) = ask(
+            endpoints,
+            system,
+            users.remove(0),
+            verbose,
+            stream
+                .then_some("Generating subject")
+                .filter(|_| !structured),
+            structured,
+        )
+        .await?;
+        (vec![answer], used)
+    } else {
+        ask_concurrently(
+            endpoints,
+            system,
+            users,
+            verbose,
+            structured,
+            "Generated subject",
+        )
+        .await?
+    };
+
+    let candidates = answers
+        .into_iter()
+        .map(|answer| to_candidate(answer, file_changes, structured))
+        .collect();
+    Ok((candidates, used))
+}
+
+/// Turns a subject answer into a `(subject, message)` pair.
+///
+/// # Arguments
+///
+/// * `answer` - The answer of the AI.
+/// * `file_changes` - The summaries of the changed files.
+/// * `structured` - Whether the answer was asked for as a JSON commit message.
+fn to_candidate(answer: String, file_changes: &str, structured: bool) -> (String, String) {
+    match CommitMessage::parse(&answer).filter(|_| structured) {
+        Some(parsed) => {
+            output::print_verbose(&format!("Parsed commit message: {:?}", parsed));
+            let message = if parsed.body.trim().is_empty() {
+                file_changes.to_string()
+            } else {
+                format!("{}\n\n{}", parsed.body.trim(), file_changes)
+            };
+            (parsed.header(), message)
+        }
+        None => {
+            if structured {
+                output::print_warning("Failed to parse the structured answer, using it as is");
+            }
+            (answer, file_changes.to_string())
+        }
+    }
+}
+
+/// Lets the user pick one of the candidate subjects in the terminal.
+///
+/// # Arguments
+///
+/// * `candidates` - The `(subject, message)` pairs to choose from.
+///
+/// # Returns
+///
+/// * `Ok(Choice)` with the pick of the user; an empty answer picks the first candidate.
+/// * `Err(AiError::Cancelled)` if the user quits or closes stdin.
+async fn pick_candidate(candidates: &[(String, String)]) -> Result<Choice, AiError> {
+    eprintln!("\nCandidate subjects:");
+    for (index, (subject, _)) in candidates.iter().enumerate() {
+        eprintln!("  {}) {}", index + 1, subject);
+    }
+    eprintln!("  r) regenerate   w) write one by hand   q) quit");
+    loop {
+        let answer = input::ask(&format!("Choose [1-{}/r/w/q] (1): ", candidates.len()))
+            .await
+            .map_err(|_| AiError::Cancelled)?;
+        match answer.as_str() {
+            "" => return Ok(Choice::Candidate(0)),
+            "r" => return Ok(Choice::Regenerate),
+            "q" => return Err(AiError::Cancelled),
+            "w" => {
+                let subject = input::ask("Subject: ")
+                    .await
+                    .map_err(|_| AiError::Cancelled)?;
+                if !subject.is_empty() {
+                    return Ok(Choice::Manual(subject));
+                }
+            }
+            number => {
+                if let Ok(number) = number.parse::<usize>()
+                    && (1..=candidates.len()).contains(&number)
+                {
+                    return Ok(Choice::Candidate(number - 1));
+                }
+                eprintln!(
+                    "Please answer with a number from 1 to {}, r, w or q",
+                    candidates.len()
+                );
+            }
+        }
+    }
+}
+
+/// What the user decided when reviewing the generated message.
+pub enum Review {
+    Accept,
+    Edit,
+    Regenerate,
+    /// Revise the subject following the feedback of the user.
+    Refine(String),
+}
+
+/// Asks the user what to do with the generated message before committing.
+///
+/// # Returns
+///
+/// * `Ok(Review)` with the decision; an empty answer accepts the message.
+/// * `Err(AiError::Cancelled)` if the user quits or closes stdin.
+pub async fn ask_review() -> Result<Review, AiError> {
+    loop {
+        let answer =
+            input::ask("Commit it? [y]es / [e]dit / [r]egenerate / [f]eedback / [q]uit (y): ")
+                .await
+                .map_err(|_| AiError::Cancelled)?;
+        match answer.to_lowercase().as_str() {
+            "" | "y" | "yes" => return Ok(Review::Accept),
+            "e" | "edit" => return Ok(Review::Edit),
+            "r" | "regenerate" => return Ok(Review::Regenerate),
+            "q" | "quit" => return Err(AiError::Cancelled),
+            "f" | "feedback" => {
+                let feedback = input::ask("Feedback, e.g. 'shorter': ")
+                    .await
+                    .map_err(|_| AiError::Cancelled)?;
+                if !feedback.is_empty() {
+                    return Ok(Review::Refine(feedback));
+                }
+            }
+            _ => eprintln!("Please answer with y, e, r, f or q"),
+        }
+    }
+}
+
+/// Lets the user edit the generated message in the git editor.
+///
+/// # Arguments
+///
+/// * `subject` - The commit subject.
+/// * `message` - The commit message body.
+///
+/// # Returns
+///
+/// * `Ok((subject, message))` split at the first blank line of the edited text, so a subject
+///   spanning several lines stays the subject as it does for git.
+/// * `Err` if the editor fails or the user removed the whole message.
+pub fn edit_commit_message(
+    subject: &str,
+    message: &str,
+) -> Result<(String, String), Box<dyn std::error::Error>> {
+    let edited = git::git_edit_message(&format!("{}\n\n{}", subject, message))?;
+    if edited.is_empty() {
+        return Err("aborting commit due to empty commit message".into());
+    }
+    let (subject, message) = edited.split_once("\n\n").unwrap_or((&edited, ""));
+    Ok((subject.trim().to_string(), message.trim().to_string()))
+}
+
+/// Asks for a new subject for the same summaries of the changed files.
+///
+/// Only the subject was rejected, so the summaries are not generated again. The request asks for
+/// a wording different from the earlier candidates, which also keeps it from hitting the cache.
+///
+/// # Arguments
+///
+/// * `endpoints` - The endpoints to try, in order.
+/// * `file_changes` - The summaries of the changed files.
+/// * `round` - How many times the subject has been regenerated, starting at 1.
+/// * `verbose` - Whether to print verbose output.
+/// * `custom_subject_prompt` - Optional custom subject prompt.
+///
+/// # Returns
+///
+/// * `Ok((subject, message))` with the new subject, or `Err(AiError)` if every endpoint fails.
+pub async fn regenerate_subject(
+    endpoints: &[Endpoint],
+    file_changes: &str,
+    round: usize,
+    verbose: bool,
+    custom_subject_prompt: Option<&str>,
+) -> Result<(String, String), AiError> {
+    // Count on from the candidates generated at first
+    let number = ai::get_candidates().max(1) + round - 1;
+    let (mut candidates, _) = generate_subjects(
+        endpoints,
+        file_changes,
+        verbose,
+        custom_subject_prompt,
+        ai::get_stream_enabled(),
+        number..number + 1,
+    )
+    .await?;
+    Ok(candidates.swap_remove(0))
+}
+
+/// Asks the AI to revise the subject following the feedback of the user.
+///
+/// The summaries of the changes, the previous answer and the feedback are sent as a conversation,
+/// so the model continues from its earlier answer instead of starting over.
+///
+/// # Arguments
+///
+/// * `endpoints` - The endpoints to try, in order.
+/// * `subject` - The current commit subject.
+/// * `message` - The current commit message body.
+/// * `file_changes` - The summaries of the changed files.
+/// * `feedback` - What the user wants changed, e.g. "shorter".
+/// * `verbose` - Whether to print verbose output.
+/// * `custom_subject_prompt` - Optional custom subject prompt.
+///
+/// # Returns
+///
+/// * `Ok((subject, message))` with the revised subject, and the revised body of a structured
+///   answer; plain answers keep the current message. `Err(AiError)` if every endpoint fails.
+pub async fn refine_subject(
+    endpoints: &[Endpoint],
+    subject: &str,
+    message: &str,
+    file_changes: &str,
+    feedback: &str,
+    verbose: bool,
+    custom_subject_prompt: Option<&str>,
+) -> Result<(String, String), AiError> {
+    let structured = ai::get_structured_enabled();
+    let mut system = prompt::get_subject_prompt(custom_subject_prompt);
+    if structured {
+        system.push_str(CommitMessage::instruction());
+    }
+    report::set_field("prompts", "subject", &system);
+    // The body written on top of the summaries belongs to the previous answer
+    let body = message.strip_suffix(file_changes).unwrap_or(message).trim();
+    let previous = if body.is_empty() {
+        subject.to_string()
+    } else {
+        format!("{}\n\n{}", subject, body)
+    };
+    let turns = vec![
+        Message {
+            role: "user".to_string(),
+            content: format!("The changes are: \n{}", file_changes),
+        },
+        Message {
+            role: "assistant".to_string(),
+            content: previous,
+        },
+        Message {
+            role: "user".to_string(),
+            content: format!("Revise it following this feedback: {}", feedback),
+        },
+    ];
+    let stream_label = ai::get_stream_enabled()
+        .then_some("Revising subject")
+        .filter(|_| !structured);
+    let (answer, _) =
+        ask_turns(endpoints, system, turns, verbose, stream_label, structured).await?;
+    let (subject, revised) = to_candidate(answer, file_changes, structured);
+    if structured {
+        Ok((subject, revised))
+    } else {
+        Ok((subject, message.to_string()))
     }
-    Ok(())
 }
@@ -1,27 +1,30 @@
 use std::io::{ErrorKind, Result};
 use toml::{Value, map::Map};
 
-use crate::config::constants::{CUSTOM_SECTION_NAME, DIFF_SIZE_LIMIT, MAX_DIFF_FILES};
-use crate::utils::output;
+use crate::config::constants::{CUSTOM_SECTION_NAME, MAX_DIFF_FILES};
+use crate::utils::{output, report};
 
 static NAME: &str = "lines_limit";
 static MAX_FILES_NAME: &str = "max_diff_files";
+static REVIEW_NAME: &str = "review";
 
-pub fn get_lines_limit() -> usize {
+/// Returns the line limit of the changes, if the user configured one.
+///
+/// The size of one request is bounded by the model's context window; this limit is an
+/// additional, optional cap, and larger changes are summarized in parts. Models without a known
+/// window fall back to `DIFF_SIZE_LIMIT`.
+pub fn get_lines_limit() -> Option<usize> {
     let lines_limit = gim_config::config::get_config_value(CUSTOM_SECTION_NAME, NAME);
     if let Err(e) = lines_limit {
         output::print_verbose(&format!(
-            "get custom config '{}' error: {:?}, return default: {}",
-            NAME, e, DIFF_SIZE_LIMIT
+            "get custom config '{}' error: {:?}, no line limit",
+            NAME, e
         ));
-        return DIFF_SIZE_LIMIT;
+        return None;
     }
-    let lines_limit = lines_limit.ok();
-    if let Some(limit) = lines_limit {
-        output::print_verbose(&format!("get custom config '{}' value: {:?}", NAME, limit));
-        return limit.as_integer().unwrap() as usize;
-    }
-    DIFF_SIZE_LIMIT
+    let limit = lines_limit.ok()?;
+    output::print_verbose(&format!("get custom config '{}' value: {:?}", NAME, limit));
+    limit.as_integer().map(|limit| limit as usize)
 }
 
 pub fn set_lines_limit(lines_limit: usize) -> Result<()> {
@@ -31,20 +34,17 @@ pub fn set_lines_limit(lines_limit: usize) -> Result<()> {
         Value::Integer(lines_limit as i64),
     );
     if let Err(e) = set {
-        output::print_verbose(&format!(
-            "get custom config '{}' error: {:?}, return default: {}",
-            NAME, e, DIFF_SIZE_LIMIT
-        ));
-        if e.kind() == ErrorKind::NotFound {
-            if e.to_string() == format!("Section '{}' not found", CUSTOM_SECTION_NAME) {
-                let mut config = gim_config::config::get_config().unwrap();
-                let map = config.as_table_mut().unwrap();
+        output::print_verbose(&format!("set custom config '{}' error: {:?}", NAME, e));
+        if e.kind() == ErrorKind::NotFound
+            && e.to_string() == format!("Section '{}' not found", CUSTOM_SECTION_NAME)
+        {
+            let mut config = gim_config::config::get_config().unwrap();
+            let map = config.as_table_mut().unwrap();
 
-                let mut update_table = Map::new();
-                update_table.insert(NAME.to_string(), Value::Integer(lines_limit as i64));
-                map.insert(CUSTOM_SECTION_NAME.to_string(), Value::Table(update_table));
-                return gim_config::config::save_config(&mut config);
-            }
+            let mut update_table = Map::new();
+            update_table.insert(NAME.to_string(), Value::Integer(lines_limit as i64));
+            map.insert(CUSTOM_SECTION_NAME.to_string(), Value::Table(update_table));
+            return gim_config::config::save_config(&config);
         }
         return Err(e);
     }
@@ -86,16 +86,16 @@ pub fn set_max_diff_files(max_files: usize) -> Result<()> {
             "set custom config '{}' error: {:?}",
             MAX_FILES_NAME, e
         ));
-        if e.kind() == ErrorKind::NotFound {
-            if e.to_string() == format!("Section '{}' not found", CUSTOM_SECTION_NAME) {
-                let mut config = gim_config::config::get_config().unwrap();
-                let map = config.as_table_mut().unwrap();
+        if e.kind() == ErrorKind::NotFound
+            && e.to_string() == format!("Section '{}' not found", CUSTOM_SECTION_NAME)
+        {
+            let mut config = gim_config::config::get_config().unwrap();
+            let map = config.as_table_mut().unwrap();
 
-                let mut update_table = Map::new();
-                update_table.insert(MAX_FILES_NAME.to_string(), Value::Integer(max_files as i64));
-                map.insert(CUSTOM_SECTION_NAME.to_string(), Value::Table(update_table));
-                return gim_config::config::save_config(&mut config);
-            }
+            let mut update_table = Map::new();
+            update_table.insert(MAX_FILES_NAME.to_string(), Value::Integer(max_files as i64));
+            map.insert(CUSTOM_SECTION_NAME.to_string(), Value::Table(update_table));
+            return gim_config::config::save_config(&config);
         }
         return Err(e);
     }
@@ -106,6 +106,49 @@ pub fn set_max_diff_files(max_files: usize) -> Result<()> {
     Ok(())
 }
 
+/// Returns whether the generated message is reviewed before committing.
+///
+/// # Returns
+///
+/// * The `review` value of the custom section, `false` if not configured.
+pub fn get_review_enabled() -> bool {
+    gim_config::config::get_config_value(CUSTOM_SECTION_NAME, REVIEW_NAME)
+        .ok()
+        .and_then(|review| review.as_bool())
+        .unwrap_or(false)
+}
+
+pub fn set_review_enabled(review: bool) -> Result<()> {
+    let set = gim_config::config::update_config_value(
+        CUSTOM_SECTION_NAME,
+        REVIEW_NAME,
+        Value::Boolean(review),
+    );
+    if let Err(e) = set {
+        output::print_verbose(&format!(
+            "set custom config '{}' error: {:?}",
+            REVIEW_NAME, e
+        ));
+        if e.kind() == ErrorKind::NotFound
+            && e.to_string() == format!("Section '{}' not found", CUSTOM_SECTION_NAME)
+        {
+            let mut config = gim_config::config::get_config().unwrap();
+            let map = config.as_table_mut().unwrap();
+
+            let mut update_table = Map::new();
+            update_table.insert(REVIEW_NAME.to_string(), Value::Boolean(review));
+            map.insert(CUSTOM_SECTION_NAME.to_string(), Value::Table(update_table));
+            return gim_config::config::save_config(&config);
+        }
+        return Err(e);
+    }
+    output::print_normal(&format!(
+        "set custom config '{}' done, value: {:?}",
+        REVIEW_NAME, review
+    ));
+    Ok(())
+}
+
 /// Gets and prints the config file location.
 ///
 /// # Returns
@@ -114,5 +157,6 @@ pub fn set_max_diff_files(max_files: usize) -> Result<()> {
 pub fn get_config_and_print() -> Result<()> {
     let config_dir = gim_config::directory::config_dir()?;
     output::print_normal(&format!("Config file location: {}", config_dir.display()));
+    report::set("config_dir", config_dir);
     Ok(())
 }
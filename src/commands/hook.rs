@@ -0,0 +1,163 @@
+use indoc::indoc;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::core::git;
+use crate::utils::{output, report};
+
+static HOOK_NAME: &str = "prepare-commit-msg";
+/// Suffix of the hook that was installed before gim, which the gim hook runs first.
+static CHAINED_SUFFIX: &str = ".gim-chained";
+/// Line identifying the hook written by gim.
+static HOOK_MARKER: &str = "# Installed by gim";
+
+/// The `prepare-commit-msg` hook.
+///
+/// Git passes the message file, the source of the message and a commit SHA. The source is empty
+/// only for a plain `git commit`; messages from `-m`, `-F`, templates, merges, squashes and amends
+/// are left alone. If gim fails the commit goes on with the message file untouched.
+static HOOK_SCRIPT: &str = indoc! {r#"
+    #!/bin/sh
+    # Installed by gim, remove with 'gim hook uninstall'
+    chained="$(dirname "$0")/prepare-commit-msg.gim-chained"
+    if [ -x "$chained" ]; then
+        "$chained" "$@" || exit $?
+    fi
+
+    # Only fill the message of a plain 'git commit'
+    [ -z "$2" ] || exit 0
+    command -v gim >/dev/null 2>&1 || exit 0
+
+    message=$(gim --print --quiet --yes) || exit 0
+    [ -n "$message" ] || exit 0
+    # Keep the comments git wrote below the generated message
+    { printf '%s\n' "$message"; cat "$1"; } > "$1.gim" && mv "$1.gim" "$1"
+    exit 0
+"#};
+
+/// Returns the paths of the hook and of the chained hook in the hooks directory.
+fn hook_paths() -> io::Result<(PathBuf, PathBuf)> {
+    let dir = git::get_hooks_dir().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "the current directory is not a git repository",
+        )
+    })?;
+    let chained = dir.join(format!("{}{}", HOOK_NAME, CHAINED_SUFFIX));
+    Ok((dir.join(HOOK_NAME), chained))
+}
+
+/// Returns whether the hook at the path was written by gim.
+fn is_gim_hook(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|script| script.contains(HOOK_MARKER))
+}
+
+/// Records the state of the hook in the JSON report.
+fn report_hook(hook: &Path, chained: &Path) {
+    report::set(
+        "hook",
+        serde_json::json!({
+            "path": hook,
+            "installed": is_gim_hook(hook),
+            "chained": chained.exists().then_some(chained),
+        }),
+    );
+}
+
+/// Installs the `prepare-commit-msg` hook into the hooks directory of the repository.
+///
+/// A hook that is already there is renamed and run by the gim hook before it fills the message.
+///
+/// # Returns
+///
+/// * `Ok(())` if the hook is installed, `Err` if a chained hook is in the way or a file cannot
+///   be written.
+pub fn install_hook() -> io::Result<()> {
+    let (hook, chained) = hook_paths()?;
+    if is_gim_hook(&hook) {
+        output::print_normal(&format!(
+            "The gim hook is already installed, updating it: {}",
+            hook.display()
+        ));
+    } else if fs::symlink_metadata(&hook).is_ok() {
+        if fs::symlink_metadata(&chained).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "both {} and {} exist, remove one of them and try again",
+                    hook.display(),
+                    chained.display()
+                ),
+            ));
+        }
+        fs::rename(&hook, &chained)?;
+        output::print_normal(&format!(
+            "Moved the existing hook to {}, the gim hook runs it first",
+            chained.display()
+        ));
+    }
+
+    if let Some(dir) = hook.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&hook, HOOK_SCRIPT)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&hook, fs::Permissions::from_mode(0o755))?;
+    }
+    output::print_normal(&format!(
+        "Installed the {} hook: {}\n'git commit' now fills in a generated message when none is given",
+        HOOK_NAME,
+        hook.display()
+    ));
+    report_hook(&hook, &chained);
+    Ok(())
+}
+
+/// Removes the gim hook and puts a chained hook back in its place.
+///
+/// # Returns
+///
+/// * `Ok(())` if the hook is removed or was not installed, `Err` if a file cannot be moved.
+pub fn uninstall_hook() -> io::Result<()> {
+    let (hook, chained) = hook_paths()?;
+    if !is_gim_hook(&hook) {
+        output::print_normal("The gim hook is not installed, nothing to remove");
+        report_hook(&hook, &chained);
+        return Ok(());
+    }
+    fs::remove_file(&hook)?;
+    if fs::symlink_metadata(&chained).is_ok() {
+        fs::rename(&chained, &hook)?;
+        output::print_normal(&format!("Restored the previous hook: {}", hook.display()));
+    }
+    output::print_normal(&format!("Removed the gim {} hook", HOOK_NAME));
+    report_hook(&hook, &chained);
+    Ok(())
+}
+
+/// Prints whether the gim hook is installed and whether it chains another hook.
+///
+/// # Returns
+///
+/// * `Ok(())` if successful, `Err` outside a git repository.
+pub fn print_hook_status() -> io::Result<()> {
+    let (hook, chained) = hook_paths()?;
+    output::print_normal(&format!("Hook: {}", hook.display()));
+    if is_gim_hook(&hook) {
+        output::print_normal("Status: installed");
+        if fs::symlink_metadata(&chained).is_ok() {
+            output::print_normal(&format!("Chained hook: {}", chained.display()));
+        }
+    } else if fs::symlink_metadata(&hook).is_ok() {
+        output::print_normal(
+            "Status: not installed, another hook is in place and 'gim hook install' would chain it",
+        );
+    } else {
+        output::print_normal("Status: not installed, run 'gim hook install' to install it");
+    }
+    report_hook(&hook, &chained);
+    Ok(())
+}
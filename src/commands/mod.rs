@@ -1,5 +1,9 @@
 pub mod ai;
+pub mod budget;
+pub mod cache;
 pub mod commit;
 pub mod config;
+pub mod hook;
 pub mod prompt;
+pub mod stats;
 pub mod update;
@@ -4,7 +4,7 @@ use std::{fs, io, path::PathBuf, process::Command};
 
 use crate::config::constants::{DIFF_PROMPT_FILE, SUBJECT_PROMPT_FILE};
 use crate::core::git;
-use crate::utils::output;
+use crate::utils::{output, report};
 
 fn file_dirs() -> io::Result<PathBuf> {
     directory::config_dir()
@@ -94,23 +94,23 @@ pub fn get_diff_prompt(custom_prompt: Option<&str>) -> String {
     let trimmed = trim_diff_prompt();
 
     // First, check for local .gim directory
-    if let Some((local_diff_path, _)) = get_local_gim_prompt_files() {
-        if local_diff_path.exists() {
-            match fs::read_to_string(&local_diff_path) {
-                Ok(content) => {
-                    output::print_verbose(&format!(
-                        "Using diff prompt from local .gim directory: {}",
-                        local_diff_path.display()
-                    ));
-                    return content;
-                }
-                Err(e) => {
-                    eprintln!(
-                        "Failed to read diff prompt from local .gim directory: {}",
-                        e
-                    );
-                    // Continue to fallback to config directory
-                }
+    if let Some((local_diff_path, _)) = get_local_gim_prompt_files()
+        && local_diff_path.exists()
+    {
+        match fs::read_to_string(&local_diff_path) {
+            Ok(content) => {
+                output::print_verbose(&format!(
+                    "Using diff prompt from local .gim directory: {}",
+                    local_diff_path.display()
+                ));
+                return content;
+            }
+            Err(e) => {
+                eprintln!(
+                    "Failed to read diff prompt from local .gim directory: {}",
+                    e
+                );
+                // Continue to fallback to config directory
             }
         }
     }
@@ -166,23 +166,23 @@ pub fn get_subject_prompt(custom_prompt: Option<&str>) -> String {
     let trimmed = trim_subject_prompt();
 
     // First, check for local .gim directory
-    if let Some((_, local_subject_path)) = get_local_gim_prompt_files() {
-        if local_subject_path.exists() {
-            match fs::read_to_string(&local_subject_path) {
-                Ok(content) => {
-                    output::print_verbose(&format!(
-                        "Using subject prompt from local .gim directory: {}",
-                        local_subject_path.display()
-                    ));
-                    return content;
-                }
-                Err(e) => {
-                    eprintln!(
-                        "Failed to read subject prompt from local .gim directory: {}",
-                        e
-                    );
-                    // Continue to fallback to config directory
-                }
+    if let Some((_, local_subject_path)) = get_local_gim_prompt_files()
+        && local_subject_path.exists()
+    {
+        match fs::read_to_string(&local_subject_path) {
+            Ok(content) => {
+                output::print_verbose(&format!(
+                    "Using subject prompt from local .gim directory: {}",
+                    local_subject_path.display()
+                ));
+                return content;
+            }
+            Err(e) => {
+                eprintln!(
+                    "Failed to read subject prompt from local .gim directory: {}",
+                    e
+                );
+                // Continue to fallback to config directory
             }
         }
     }
@@ -312,9 +312,10 @@ pub fn handle_prompt_command(
                         .status()?;
                 } else {
                     // Linux and others
-                    if let Err(_) = Command::new("xdg-open")
+                    if Command::new("xdg-open")
                         .arg(file_path.parent().unwrap_or_else(|| ".".as_ref()))
                         .status()
+                        .is_err()
                     {
                         return Err(std::io::Error::new(
                             std::io::ErrorKind::NotFound,
@@ -326,7 +327,7 @@ pub fn handle_prompt_command(
             }
         } else {
             open_config_directory()?;
-            indoc::printdoc!(
+            output::print_block(&indoc::formatdoc!(
                 r#"
                 Please edit the prompt files using your favorite editor in the popped window: {}
                 1: {}
@@ -335,11 +336,11 @@ pub fn handle_prompt_command(
                 config_dir.display(),
                 DIFF_PROMPT_FILE,
                 SUBJECT_PROMPT_FILE
-            );
+            ));
         }
     } else {
         // Show the content of both prompt files
-        indoc::printdoc!(
+        output::print_block(&indoc::formatdoc!(
             r#"
             === Diff Prompt ===
             {}
@@ -349,7 +350,9 @@ pub fn handle_prompt_command(
             "#,
             &diff_prompt,
             &subject_prompt
-        );
+        ));
+        report::set_field("prompts", "diff", &diff_prompt);
+        report::set_field("prompts", "subject", &subject_prompt);
     }
 
     Ok(())
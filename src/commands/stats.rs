@@ -0,0 +1,274 @@
+use std::collections::BTreeMap;
+
+use chrono::Local;
+use serde::Deserialize;
+
+use crate::cli::StatsPeriod;
+use crate::core::ai::client::Endpoint;
+use crate::core::ai::provider::openai::matches_pattern;
+use crate::core::ai::types::Usage;
+use crate::core::{git, ledger};
+use crate::utils::{output, report};
+
+static PRICING_SECTION: &str = "pricing";
+static MODELS_KEY: &str = "models";
+static DEFAULT_CURRENCY: &str = "USD";
+
+/// The price of a model per million tokens, an entry of the `[pricing.models]` table.
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct Price {
+    pub input: f64,
+    pub output: f64,
+}
+
+/// Returns the currency the prices are given in.
+///
+/// # Returns
+///
+/// * The `currency` value of the pricing section, `USD` if not configured.
+pub fn get_currency() -> String {
+    gim_config::config::get_config_value(PRICING_SECTION, "currency")
+        .ok()
+        .and_then(|v| v.as_str().map(|s| s.to_string()))
+        .unwrap_or_else(|| DEFAULT_CURRENCY.to_string())
+}
+
+/// Returns the configured price of a model.
+///
+/// Keys of `[pricing.models]` are model prefixes or globs; the longest matching key wins.
+///
+/// # Arguments
+///
+/// * `model_name` - The name of the AI model.
+///
+/// # Returns
+///
+/// * `Some(Price)` if a key matches the model, `None` otherwise.
+pub fn get_price(model_name: &str) -> Option<Price> {
+    let models = gim_config::config::get_config_value(PRICING_SECTION, MODELS_KEY).ok()?;
+    let models = models.as_table()?;
+    models
+        .iter()
+        .filter(|(pattern, _)| matches_pattern(pattern, model_name))
+        .max_by_key(|(pattern, _)| pattern.len())
+        .and_then(|(pattern, price)| match price.clone().try_into::<Price>() {
+            Ok(price) => Some(price),
+            Err(e) => {
+                output::print_warning(&format!(
+                    "Ignore invalid price of '{}' in [{}.{}]: {}",
+                    pattern, PRICING_SECTION, MODELS_KEY, e
+                ));
+                None
+            }
+        })
+}
+
+/// Estimates the spend of a request from the configured price of the model.
+///
+/// # Returns
+///
+/// * `Some(cost)` in the configured currency, `None` if the model has no price.
+pub fn estimate_cost(model_name: &str, usage: &Usage) -> Option<f64> {
+    get_price(model_name).map(|price| {
+        (usage.prompt_tokens as f64 * price.input + usage.completion_tokens as f64 * price.output)
+            / 1_000_000.0
+    })
+}
+
+/// Reports the token usage of a request in verbose mode and records it in the ledger.
+///
+/// # Arguments
+///
+/// * `endpoint` - The endpoint that answered.
+/// * `usage` - The token counts, `None` if the provider did not report them.
+/// * `estimate` - The token counts estimated before the request, recorded if none were reported.
+pub fn record_usage(endpoint: &Endpoint, usage: Option<Usage>, estimate: &Usage) {
+    let estimated = usage.is_none();
+    let usage = usage.unwrap_or_else(|| {
+        // Budgets keep applying to providers and gateways that omit the usage
+        output::print_verbose(&format!(
+            "Model '{}' reported no token usage, recording the estimate",
+            endpoint.model
+        ));
+        *estimate
+    });
+    let cost = estimate_cost(&endpoint.model, &usage);
+    output::print_verbose(&format!(
+        "Token usage of model '{}': {} prompt + {} completion = {} tokens{}",
+        endpoint.model,
+        usage.prompt_tokens,
+        usage.completion_tokens,
+        usage.total(),
+        cost.map_or(String::new(), |cost| format!(
+            " (about {:.6} {})",
+            cost,
+            get_currency()
+        ))
+    ));
+
+    let entry = ledger::Entry {
+        time: Local::now(),
+        repo: git::get_git_root()
+            .map(|root| root.display().to_string())
+            .unwrap_or_default(),
+        provider: endpoint.provider.name().to_string(),
+        model: endpoint.model.clone(),
+        prompt_tokens: usage.prompt_tokens,
+        completion_tokens: usage.completion_tokens,
+        cost,
+        estimated,
+    };
+    if let Err(e) = ledger::append(&entry) {
+        output::print_warning(&format!("Failed to record ai usage: {}", e));
+    }
+}
+
+/// Totals of one row of the stats table.
+#[derive(Default)]
+struct Totals {
+    calls: u64,
+    usage: Usage,
+    cost: f64,
+    /// Whether some requests had no price, so the cost is a lower bound.
+    unpriced: bool,
+    /// Whether some token counts were estimated because the provider did not report them.
+    estimated: bool,
+}
+
+impl Totals {
+    fn add(&mut self, usage: Usage, cost: Option<f64>, estimated: bool) {
+        self.calls += 1;
+        self.estimated |= estimated;
+        self.usage.prompt_tokens += usage.prompt_tokens;
+        self.usage.completion_tokens += usage.completion_tokens;
+        match cost {
+            Some(cost) => self.cost += cost,
+            None => self.unpriced = true,
+        }
+    }
+
+    fn absorb(&mut self, other: &Totals) {
+        self.calls += other.calls;
+        self.usage.prompt_tokens += other.usage.prompt_tokens;
+        self.usage.completion_tokens += other.usage.completion_tokens;
+        self.cost += other.cost;
+        self.unpriced |= other.unpriced;
+        self.estimated |= other.estimated;
+    }
+
+    /// Returns the row as an object of the JSON report, without period and model for the total.
+    fn to_json(
+        &self,
+        period: Option<&str>,
+        model: Option<&str>,
+        currency: &str,
+    ) -> serde_json::Value {
+        serde_json::json!({
+            "period": period,
+            "model": model,
+            "calls": self.calls,
+            "prompt_tokens": self.usage.prompt_tokens,
+            "completion_tokens": self.usage.completion_tokens,
+            "cost": self.cost,
+            "currency": currency,
+            // Some requests had no price, the cost is a lower bound
+            "unpriced": self.unpriced,
+            "estimated": self.estimated,
+        })
+    }
+
+    fn format_cost(&self, currency: &str) -> String {
+        if self.unpriced && self.cost == 0.0 {
+            "-".to_string()
+        } else {
+            format!(
+                "{}{:.4} {}",
+                if self.unpriced { ">=" } else { "" },
+                self.cost,
+                currency
+            )
+        }
+    }
+}
+
+/// Prints the recorded token usage and estimated spend grouped by period and model.
+///
+/// # Arguments
+///
+/// * `period` - The length of the periods to group by.
+/// * `last` - How many of the most recent periods to show.
+/// * `all_repos` - Whether to include every repository instead of the current one.
+pub fn print_stats(period: StatsPeriod, last: usize, all_repos: bool) -> std::io::Result<()> {
+    let repo = if all_repos {
+        None
+    } else {
+        git::get_git_root().map(|root| root.display().to_string())
+    };
+    let entries: Vec<ledger::Entry> = ledger::read_entries()?
+        .into_iter()
+        .filter(|entry| repo.as_ref().is_none_or(|repo| &entry.repo == repo))
+        .collect();
+
+    output::print_normal(&format!(
+        "AI usage of {}",
+        repo.as_deref().unwrap_or("all repositories")
+    ));
+    report::set("repo", &repo);
+    if entries.is_empty() {
+        output::print_normal("No usage recorded yet.");
+        report::set("stats", Vec::<()>::new());
+        return Ok(());
+    }
+
+    let format = match period {
+        StatsPeriod::Day => "%Y-%m-%d",
+        StatsPeriod::Week => "%G-W%V",
+        StatsPeriod::Month => "%Y-%m",
+    };
+    let mut rows: BTreeMap<String, BTreeMap<String, Totals>> = BTreeMap::new();
+    for entry in &entries {
+        rows.entry(entry.time.format(format).to_string())
+            .or_default()
+            .entry(entry.model.clone())
+            .or_default()
+            .add(entry.usage(), entry.cost, entry.estimated);
+    }
+
+    let currency = get_currency();
+    let mut total = Totals::default();
+    output::print_normal(&format!(
+        "{:<12} {:<24} {:>6} {:>12} {:>12} {:>16}",
+        "Period", "Model", "Calls", "Prompt", "Completion", "Cost"
+    ));
+    for (key, models) in rows.iter().rev().take(last.max(1)) {
+        for (model, totals) in models {
+            output::print_normal(&format!(
+                "{:<12} {:<24} {:>6} {:>12} {:>12} {:>16}",
+                key,
+                model,
+                totals.calls,
+                totals.usage.prompt_tokens,
+                totals.usage.completion_tokens,
+                totals.format_cost(&currency)
+            ));
+            report::push("stats", totals.to_json(Some(key), Some(model), &currency));
+            total.absorb(totals);
+        }
+    }
+    output::print_normal(&format!(
+        "{:<12} {:<24} {:>6} {:>12} {:>12} {:>16}",
+        "Total",
+        "",
+        total.calls,
+        total.usage.prompt_tokens,
+        total.usage.completion_tokens,
+        total.format_cost(&currency)
+    ));
+    if total.estimated {
+        output::print_normal(
+            "Some providers reported no token usage, their requests are counted by estimate",
+        );
+    }
+    report::set("total", total.to_json(None, None, &currency));
+    Ok(())
+}
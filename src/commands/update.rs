@@ -41,7 +41,7 @@ pub fn check_update_reminder() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
     }
-    output::print_verbose(&format!("[background] End checking new version"));
+    output::print_verbose("[background] End checking new version");
     Ok(())
 }
 
@@ -74,7 +74,7 @@ fn new_version_available() -> Result<(bool, Version, Version), Box<dyn std::erro
         "[background] Local version: {}; Remote Version: {}",
         current, latest
     ));
-    Ok((&latest > &current, current, latest))
+    Ok((latest > current, current, latest))
 }
 
 /// Gets the latest version from Homebrew
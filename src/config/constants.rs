@@ -2,6 +2,8 @@ pub const REPOSITORY: &str = "git-intelligence-message";
 
 pub const DIFF_PROMPT_FILE: &str = "diff_prompt.txt";
 pub const SUBJECT_PROMPT_FILE: &str = "subject_prompt.txt";
+pub const USAGE_LEDGER_FILE: &str = "usage.jsonl";
+pub const RESPONSE_CACHE_DIR: &str = "cache";
 
 pub const CUSTOM_SECTION_NAME: &str = "user";
 pub const DIFF_SIZE_LIMIT: usize = 1000;
@@ -6,3 +6,10 @@ pub const DOUBAO_URL: &str = "https://ark.cn-beijing.volces.com/api/v3/chat/comp
 pub const GLM_URL: &str = "https://open.bigmodel.cn/api/paas/v4/chat/completions";
 pub const DEEPSEEK_URL: &str = "https://api.deepseek.com/chat/completions";
 pub const QIANFAN_URL: &str = "https://qianfan.baidubce.com/v2/chat/completions";
+pub const ANTHROPIC_URL: &str = "https://api.anthropic.com/v1/messages";
+pub const OLLAMA_URL: &str = "http://localhost:11434/api/chat";
+pub const AZURE_HOST_SUFFIX: &str = ".openai.azure.com";
+pub const AZURE_API_VERSION: &str = "2024-10-21";
+/// The first Azure OpenAI API version accepting `stream_options`.
+pub const AZURE_STREAM_USAGE_API_VERSION: &str = "2024-09-01";
+pub const LLAMACPP_URL: &str = "http://localhost:8080/v1/chat/completions";
@@ -1,125 +1,299 @@
-use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::config::urls;
-use crate::core::ai::types::{Message, Request, Response};
+use crate::core::ai::error::AiError;
+use crate::core::ai::provider::{Provider, Registry, StreamEvent};
+use crate::core::ai::retry::{self, RetryPolicy};
+use crate::core::ai::transport::Transport;
+use crate::core::ai::types::{Message, Usage};
 use crate::utils::output;
 
-/// Sends a chat request to the specified AI API endpoint and returns the response.
+/// Default time allowed to establish a connection to the provider.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Default time allowed for a whole request, including reading the (streamed) answer.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The answer of a chat request.
+pub struct Completion {
+    pub text: String,
+    /// The token counts, `None` if the provider did not report them.
+    pub usage: Option<Usage>,
+}
+
+/// A resolved AI endpoint: the provider speaking its wire format plus the request target.
+#[derive(Clone)]
+pub struct Endpoint {
+    pub provider: Arc<dyn Provider>,
+    pub url: String,
+    pub model: String,
+    pub api_key: String,
+    pub retry: RetryPolicy,
+    pub connect_timeout: Duration,
+    pub timeout: Duration,
+    pub transport: Transport,
+}
+
+impl Endpoint {
+    /// Resolves the endpoint for the configured url and model through the registry.
+    ///
+    /// # Arguments
+    ///
+    /// * `registry` - The providers to choose from.
+    /// * `url` - The configured API url, may be empty to use the model's default.
+    /// * `model_name` - The name of the AI model.
+    /// * `api_key` - The API key for authentication.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Endpoint)` if a provider and url could be determined.
+    /// * `Err(AiError::ConfigMissing)` if no url is configured and the model is not recognized.
+    pub fn resolve(
+        registry: &Registry,
+        url: String,
+        model_name: String,
+        api_key: String,
+    ) -> Result<Self, AiError> {
+        let (provider, url) = registry.resolve(&model_name, &url).ok_or_else(|| {
+            AiError::ConfigMissing(format!(
+                "no url configured and model '{}' has no default, please setup ai url by 'gim ai -u <url>'",
+                model_name
+            ))
+        })?;
+        Ok(Self {
+            transport: provider.transport(),
+            provider,
+            url,
+            model: model_name,
+            api_key,
+            retry: RetryPolicy::default(),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            timeout: DEFAULT_TIMEOUT,
+        })
+    }
+}
+
+/// Sends a chat request to the endpoint and returns the response.
+///
+/// The request body, authentication and response parsing are delegated to the endpoint's provider.
 ///
 /// # Arguments
 ///
-/// * `url` - The API endpoint URL.
-/// * `model_name` - The name of the AI model to use.
-/// * `api_key` - The API key for authentication.
+/// * `endpoint` - The resolved endpoint to talk to.
 /// * `system` - Optional system prompt.
-/// * `user` - The user input or prompt.
+/// * `messages` - The turns of the conversation, ending with the user input.
 /// * `log_info` - Whether to print verbose log information.
 ///
 /// # Returns
 ///
-/// * `Ok(String)` containing the AI response if successful.
-/// * `Err(Box<dyn Error>)` if the request fails or the response is invalid.
+/// * `Ok(Completion)` containing the AI response if successful.
+/// * `Err(AiError)` if the request fails or the response is invalid.
 pub async fn chat(
-    url: String,
-    model_name: String,
-    api_key: String,
+    endpoint: &Endpoint,
     system: Option<String>,
-    user: String,
+    messages: &[Message],
     log_info: bool,
-) -> Result<String, Box<dyn Error>> {
-    let mut request_body = Request {
-        model: model_name.clone(),
-        messages: vec![Message {
-            role: "user".to_string(),
-            content: user,
-        }],
-        ..Default::default()
-    };
-    if let Some(system) = system {
-        request_body.messages.push(Message {
-            role: "system".to_string(),
-            content: system,
-        });
-    }
-    let mut url = url;
-    if !url.starts_with("http") {
-        if let Some(str) = get_url_by_model(&model_name) {
-            url = str;
-        } else {
-            eprintln!("Error: please setup ai url first");
-            std::process::exit(1);
-        }
-    }
+) -> Result<Completion, AiError> {
+    let request_body =
+        endpoint
+            .provider
+            .build_request(&endpoint.model, system.as_deref(), messages);
+    complete(endpoint, &request_body, log_info).await
+}
 
-    if log_info {
-        output::print_normal(&format!("ai request url: {}", url));
+/// Sends a chat request asking for an answer matching a JSON schema.
+///
+/// Providers without a JSON mode are asked with a regular request; the prompt itself should then
+/// ask for JSON and the caller must be prepared to parse text.
+///
+/// # Arguments
+///
+/// * `endpoint` - The resolved endpoint to talk to.
+/// * `system` - Optional system prompt.
+/// * `messages` - The turns of the conversation, ending with the user input.
+/// * `log_info` - Whether to print verbose log information.
+/// * `name` - The name of the schema.
+/// * `schema` - The JSON schema the answer should match.
+///
+/// # Returns
+///
+/// * `Ok(Completion)` containing the AI response if successful.
+/// * `Err(AiError)` if the request fails or the response is invalid.
+pub async fn chat_json(
+    endpoint: &Endpoint,
+    system: Option<String>,
+    messages: &[Message],
+    log_info: bool,
+    name: &str,
+    schema: &serde_json::Value,
+) -> Result<Completion, AiError> {
+    let provider = &endpoint.provider;
+    let mut request_body = provider.build_request(&endpoint.model, system.as_deref(), messages);
+    if !provider.enable_json_schema(&mut request_body, name, schema) {
+        output::print_verbose(&format!(
+            "provider '{}' has no json mode, parsing the answer as text",
+            provider.name()
+        ));
     }
+    complete(endpoint, &request_body, log_info).await
+}
 
-    // Send request
-    let client = reqwest::Client::new();
-    let response = client
-        .post(url)
-        .header("Authorization", format!("Bearer {}", &api_key))
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()
-        .await?;
-    let status = response.status();
-    if status.as_u16() >= 400 {
-        return Err(format!("ai request failed: {}", status).into());
-    }
+/// Sends a request body built by the endpoint's provider and parses the whole answer.
+async fn complete(
+    endpoint: &Endpoint,
+    request_body: &serde_json::Value,
+    log_info: bool,
+) -> Result<Completion, AiError> {
+    let provider = &endpoint.provider;
+    let response = send(endpoint, request_body, log_info).await?;
 
     let res_text = response.text().await?;
     if log_info {
-        output::print_verbose(&format!("ai request result ({}): {}", status, res_text));
+        output::print_verbose(&format!("ai request result: {}", res_text));
     }
-
-    let res: Response = serde_json::from_str(&res_text)?;
-
-    if let Some(res) = res.choices {
-        return Ok(res[0].message.content.clone());
-    }
-    eprintln!("{:?}", res);
-    if let Some(res) = res.error {
-        return Err(res.message.into());
-    }
-    Err("unkown exception".into())
+    let text = provider.parse_response(&res_text)?;
+    Ok(Completion {
+        text,
+        usage: provider.parse_usage(&res_text),
+    })
 }
 
-/// Returns the default API URL for the given model name, if recognized.
+/// Sends a chat request and streams the answer as server-sent events.
+///
+/// Every piece of the answer is handed to `on_delta` as soon as it arrives. Providers without
+/// streaming support are asked with a regular request and the whole answer is handed over at once.
 ///
 /// # Arguments
 ///
-/// * `model_name` - The name of the AI model.
+/// * `endpoint` - The resolved endpoint to talk to.
+/// * `system` - Optional system prompt.
+/// * `messages` - The turns of the conversation, ending with the user input.
+/// * `log_info` - Whether to print verbose log information.
+/// * `on_delta` - Callback receiving each piece of the answer text.
 ///
 /// # Returns
 ///
-/// * `Some(String)` containing the default URL if the model is recognized.
-/// * `None` if the model is not recognized.
-pub fn get_url_by_model(model_name: &str) -> Option<String> {
-    if model_name.starts_with("moonshot") {
-        return Some(urls::MONOSHOT_URL.to_string());
-    }
-    if model_name.starts_with("qwen") {
-        return Some(urls::QWEN_URL.to_string());
-    }
-    if model_name.starts_with("gpt") {
-        return Some(urls::GPT_URL.to_string());
-    }
-    if model_name.starts_with("gemini") {
-        return Some(urls::GEMINI_URL.to_string());
+/// * `Ok(Completion)` containing the assembled AI response if successful.
+/// * `Err(AiError)` if the request fails or a streamed event is invalid.
+pub async fn chat_stream<F: FnMut(&str)>(
+    endpoint: &Endpoint,
+    system: Option<String>,
+    messages: &[Message],
+    log_info: bool,
+    mut on_delta: F,
+) -> Result<Completion, AiError> {
+    let provider = &endpoint.provider;
+    let mut request_body = provider.build_request(&endpoint.model, system.as_deref(), messages);
+    if !provider.enable_streaming(&mut request_body) {
+        output::print_verbose(&format!(
+            "provider '{}' does not support streaming, waiting for the full answer",
+            provider.name()
+        ));
+        let completion = chat(endpoint, system, messages, log_info).await?;
+        on_delta(&completion.text);
+        return Ok(completion);
     }
-    if model_name.starts_with("doubao") {
-        return Some(urls::DOUBAO_URL.to_string());
+    let mut response = send(endpoint, &request_body, log_info).await?;
+
+    let mut answer = String::new();
+    let mut usage: Option<Usage> = None;
+    let mut buffer: Vec<u8> = Vec::new();
+    'read: while let Some(chunk) = response.chunk().await? {
+        buffer.extend_from_slice(&chunk);
+        // Events may be split across chunks, only handle complete lines
+        while let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let Some(data) = line.trim().strip_prefix("data:") else {
+                continue;
+            };
+            match provider.parse_stream_event(data.trim())? {
+                StreamEvent::Delta(text) => {
+                    on_delta(&text);
+                    answer.push_str(&text);
+                }
+                StreamEvent::Usage(counts) => {
+                    usage = Some(usage.unwrap_or_default().merge(counts));
+                }
+                StreamEvent::Done => break 'read,
+                StreamEvent::Skip => {}
+            }
+        }
     }
-    if model_name.starts_with("glm") {
-        return Some(urls::GLM_URL.to_string());
+    if log_info {
+        output::print_verbose(&format!("ai request result (streamed): {}", answer));
     }
-    if model_name.starts_with("deepseek") {
-        return Some(urls::DEEPSEEK_URL.to_string());
+    Ok(Completion {
+        text: answer,
+        usage,
+    })
+}
+
+/// Posts the request body to the endpoint and turns error statuses into provider errors.
+///
+/// Rate limits, server errors and transient network failures are retried according to the
+/// endpoint's retry policy, honoring `Retry-After`; other failures are returned immediately.
+async fn send(
+    endpoint: &Endpoint,
+    request_body: &serde_json::Value,
+    log_info: bool,
+) -> Result<reqwest::Response, AiError> {
+    let provider = &endpoint.provider;
+    if log_info {
+        output::print_normal(&format!(
+            "ai request url: {} ({})",
+            endpoint.url,
+            provider.name()
+        ));
     }
-    if model_name.starts_with("qianfan") {
-        return Some(urls::QIANFAN_URL.to_string());
+
+    let client = endpoint
+        .transport
+        .build_client(endpoint.connect_timeout, endpoint.timeout)?;
+    let extra_headers = endpoint.transport.header_map()?;
+    let max_attempts = endpoint.retry.max_attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        let mut request = client
+            .post(&endpoint.url)
+            .header("Content-Type", "application/json");
+        for (name, value) in provider.auth_headers(&endpoint.api_key) {
+            request = request.header(name, value);
+        }
+        request = request.headers(extra_headers.clone());
+
+        let (reason, delay) = match request.json(request_body).send().await {
+            Ok(response) if response.status().as_u16() < 400 => return Ok(response),
+            Ok(response) => {
+                let status = response.status();
+                let delay = endpoint.retry.retry_after(response.headers());
+                let res_text = response.text().await.unwrap_or_default();
+                if log_info {
+                    output::print_verbose(&format!("ai request result ({}): {}", status, res_text));
+                }
+                let message = provider.parse_error(&res_text).unwrap_or(res_text);
+                let error = AiError::from_status(status, message);
+                if !retry::is_retryable_status(status) || attempt >= max_attempts {
+                    return Err(error);
+                }
+                (error.to_string(), delay)
+            }
+            Err(e) => {
+                if !retry::is_retryable_error(&e) || attempt >= max_attempts {
+                    return Err(e.into());
+                }
+                (e.to_string(), None)
+            }
+        };
+
+        let delay = delay.unwrap_or_else(|| endpoint.retry.backoff(attempt));
+        output::print_warning(&format!(
+            "{}; retrying in {:.1}s (attempt {}/{})",
+            reason,
+            delay.as_secs_f32(),
+            attempt + 1,
+            max_attempts
+        ));
+        tokio::time::sleep(delay).await;
+        attempt += 1;
     }
-    None
 }
@@ -0,0 +1,146 @@
+use std::fmt::{Display, Formatter};
+
+use reqwest::StatusCode;
+
+/// Everything that can go wrong while asking an AI provider.
+///
+/// Variants carry the provider's own error message where there is one, and each maps to a
+/// distinct process exit code so scripts can tell failures apart.
+#[derive(Debug)]
+pub enum AiError {
+    /// The ai section of the config file lacks a required value.
+    ConfigMissing(String),
+    /// A value of the config file cannot be used, e.g. a malformed proxy url.
+    ConfigInvalid(String),
+    /// The provider rejected the API key.
+    Auth { status: StatusCode, message: String },
+    /// Too many requests; retrying later may succeed.
+    RateLimited { message: String },
+    /// The account has run out of credit or quota.
+    Quota { message: String },
+    /// Any other error status returned by the provider.
+    Http { status: StatusCode, message: String },
+    /// The provider answered, but not in the expected shape.
+    BadResponse(String),
+    /// The request did not complete in time.
+    Timeout,
+    /// The provider could not be reached.
+    Network(String),
+    /// The request would exceed a hard cap of the `[budget]` section.
+    BudgetExceeded(String),
+    /// The user interrupted the request with Ctrl+C.
+    Cancelled,
+}
+
+impl AiError {
+    /// Classifies an error status returned by a provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The HTTP status of the response.
+    /// * `message` - The provider's error message, or the raw body if it could not be parsed.
+    pub fn from_status(status: StatusCode, message: String) -> Self {
+        let lower = message.to_lowercase();
+        match status.as_u16() {
+            401 | 403 => AiError::Auth { status, message },
+            402 => AiError::Quota { message },
+            429 if lower.contains("quota") || lower.contains("billing") => {
+                AiError::Quota { message }
+            }
+            429 => AiError::RateLimited { message },
+            _ => AiError::Http { status, message },
+        }
+    }
+
+    /// Returns the process exit code for this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            AiError::ConfigMissing(_) | AiError::ConfigInvalid(_) => 3,
+            AiError::Auth { .. } => 4,
+            AiError::RateLimited { .. } => 5,
+            AiError::Quota { .. } => 6,
+            AiError::Http { .. } => 7,
+            AiError::BadResponse(_) => 8,
+            AiError::Timeout => 9,
+            AiError::Network(_) => 10,
+            AiError::BudgetExceeded(_) => 11,
+            // The conventional code of a process terminated by SIGINT
+            AiError::Cancelled => 130,
+        }
+    }
+}
+
+impl Display for AiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AiError::ConfigMissing(what) => write!(
+                f,
+                "ai config is incomplete: {}. Run 'gim ai' to check the configuration",
+                what
+            ),
+            AiError::ConfigInvalid(what) => write!(
+                f,
+                "ai config is invalid: {}. Fix it in the config file shown by 'gim config --show-location'",
+                what
+            ),
+            AiError::Auth { status, message } => write!(
+                f,
+                "ai authentication failed ({}): {}. Check your api key with 'gim ai -k' and set it with 'gim ai -k <apikey>'",
+                status, message
+            ),
+            AiError::RateLimited { message } => write!(
+                f,
+                "ai request was rate limited: {}. Wait a moment and try again, or configure [[ai.fallbacks]]",
+                message
+            ),
+            AiError::Quota { message } => write!(
+                f,
+                "ai quota exhausted: {}. Top up your account or switch the model with 'gim ai -m <model>'",
+                message
+            ),
+            AiError::Http { status, message } => {
+                write!(f, "ai request failed: {} ({})", status, message)
+            }
+            AiError::BadResponse(detail) => write!(
+                f,
+                "unexpected ai response: {}. Check that the url speaks the protocol of the provider listed by 'gim ai providers'",
+                detail
+            ),
+            AiError::Timeout => write!(
+                f,
+                "ai request timed out. The provider may be overloaded, try again later or raise 'timeout_secs' in the ai section"
+            ),
+            AiError::Network(detail) => write!(
+                f,
+                "could not reach the ai provider: {}. Check the url with 'gim ai' and your network",
+                detail
+            ),
+            AiError::BudgetExceeded(detail) => write!(
+                f,
+                "ai request refused, it would exceed the {}. Raise the caps in the [budget] section or pass --ignore-budget",
+                detail
+            ),
+            AiError::Cancelled => write!(f, "ai request cancelled, nothing was committed"),
+        }
+    }
+}
+
+impl std::error::Error for AiError {}
+
+impl From<reqwest::Error> for AiError {
+    fn from(error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            AiError::Timeout
+        } else if error.is_decode() {
+            AiError::BadResponse(error.to_string())
+        } else {
+            AiError::Network(error.to_string())
+        }
+    }
+}
+
+impl From<serde_json::Error> for AiError {
+    fn from(error: serde_json::Error) -> Self {
+        AiError::BadResponse(error.to_string())
+    }
+}
@@ -1,2 +1,7 @@
 pub mod client;
+pub mod error;
+pub mod provider;
+pub mod retry;
+pub mod tokens;
+pub mod transport;
 pub mod types;
@@ -0,0 +1,234 @@
+pub mod anthropic;
+pub mod azure;
+pub mod ollama;
+pub mod openai;
+
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::config::urls;
+use crate::core::ai::error::AiError;
+use crate::core::ai::transport::Transport;
+use crate::core::ai::types::{Message, Usage};
+use anthropic::Anthropic;
+use azure::AzureOpenAi;
+use ollama::Ollama;
+use openai::OpenAiCompatible;
+
+/// How the API key is attached to a request.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuthStyle {
+    /// `Authorization: Bearer <key>`
+    #[default]
+    Bearer,
+    /// `x-api-key: <key>`
+    XApiKey,
+    /// `api-key: <key>`
+    ApiKey,
+    /// No authentication header at all.
+    None,
+}
+
+impl AuthStyle {
+    /// Returns the headers carrying the API key in this style.
+    pub fn headers(&self, api_key: &str) -> Vec<(String, String)> {
+        match self {
+            AuthStyle::Bearer => vec![("Authorization".to_string(), format!("Bearer {}", api_key))],
+            AuthStyle::XApiKey => vec![("x-api-key".to_string(), api_key.to_string())],
+            AuthStyle::ApiKey => vec![("api-key".to_string(), api_key.to_string())],
+            AuthStyle::None => Vec::new(),
+        }
+    }
+}
+
+/// One parsed `data:` line of a server-sent event stream.
+#[derive(Debug)]
+pub enum StreamEvent {
+    /// A piece of the answer text.
+    Delta(String),
+    /// The answer is complete.
+    Done,
+    /// Token counts, possibly partial; later events are merged into earlier ones.
+    Usage(Usage),
+    /// An event carrying no answer text, e.g. a ping.
+    Skip,
+}
+
+/// A chat completion vendor: knows where its models live, how to authenticate
+/// and how to translate prompts to and from its wire format.
+pub trait Provider: Send + Sync {
+    /// Returns the unique name of the provider, e.g. `openai` or `anthropic`.
+    fn name(&self) -> &str;
+
+    /// Returns true if the provider serves the given model by default.
+    fn matches_model(&self, model_name: &str) -> bool;
+
+    /// Returns the model prefix or glob shown to users, if the provider serves models by default.
+    fn model_pattern(&self) -> Option<String>;
+
+    /// Returns true if the url is an endpoint that only this provider's wire format understands.
+    fn matches_url(&self, _url: &str) -> bool {
+        false
+    }
+
+    /// Returns the endpoint used when the user has not configured an url.
+    fn default_url(&self) -> Option<String>;
+
+    /// Returns false if the provider works without an API key, e.g. a local server.
+    fn requires_api_key(&self) -> bool {
+        true
+    }
+
+    /// Builds the JSON request body for a chat.
+    ///
+    /// # Arguments
+    ///
+    /// * `model_name` - The name of the AI model to use.
+    /// * `system` - Optional system prompt.
+    /// * `messages` - The turns of the conversation, ending with the user input.
+    fn build_request(
+        &self,
+        model_name: &str,
+        system: Option<&str>,
+        messages: &[Message],
+    ) -> serde_json::Value;
+
+    /// Returns the proxy, root certificate and extra header settings of the provider.
+    fn transport(&self) -> Transport {
+        Transport::default()
+    }
+
+    /// Returns the HTTP headers carrying the API key.
+    fn auth_headers(&self, api_key: &str) -> Vec<(String, String)>;
+
+    /// Extracts the answer text from a successful response body.
+    fn parse_response(&self, body: &str) -> Result<String, AiError>;
+
+    /// Extracts the token counts from a successful response body, if the provider reports them.
+    fn parse_usage(&self, _body: &str) -> Option<Usage> {
+        None
+    }
+
+    /// Extracts the provider's error message from the body of a failed response.
+    fn parse_error(&self, body: &str) -> Option<String>;
+
+    /// Switches a request body built by `build_request` to server-sent event streaming.
+    ///
+    /// # Returns
+    ///
+    /// * `false` if the provider cannot stream; the body is left untouched.
+    fn enable_streaming(&self, _request_body: &mut serde_json::Value) -> bool {
+        false
+    }
+
+    /// Asks for an answer matching a JSON schema in a request body built by `build_request`.
+    ///
+    /// # Arguments
+    ///
+    /// * `request_body` - The request body to change.
+    /// * `name` - The name of the schema.
+    /// * `schema` - The JSON schema the answer must match.
+    ///
+    /// # Returns
+    ///
+    /// * `false` if the provider has no JSON mode; the body is left untouched.
+    fn enable_json_schema(
+        &self,
+        _request_body: &mut serde_json::Value,
+        _name: &str,
+        _schema: &serde_json::Value,
+    ) -> bool {
+        false
+    }
+
+    /// Parses the payload of one `data:` line of a streamed answer.
+    fn parse_stream_event(&self, _data: &str) -> Result<StreamEvent, AiError> {
+        Err(AiError::BadResponse(format!(
+            "provider '{}' does not support streaming",
+            self.name()
+        )))
+    }
+}
+
+/// An ordered collection of providers; the first match wins.
+#[derive(Default)]
+pub struct Registry {
+    providers: Vec<Arc<dyn Provider>>,
+}
+
+impl Registry {
+    /// Appends the built-in providers after the ones already registered.
+    pub fn register_builtin(&mut self) {
+        // Only OpenAI itself is known to accept a strict JSON schema and `stream_options`
+        let compatible = [
+            ("moonshot", "moonshot", urls::MONOSHOT_URL, false),
+            ("qwen", "qwen", urls::QWEN_URL, false),
+            ("openai", "gpt", urls::GPT_URL, true),
+            ("gemini", "gemini", urls::GEMINI_URL, false),
+            ("doubao", "doubao", urls::DOUBAO_URL, false),
+            ("glm", "glm", urls::GLM_URL, false),
+            ("deepseek", "deepseek", urls::DEEPSEEK_URL, false),
+            ("qianfan", "qianfan", urls::QIANFAN_URL, false),
+        ];
+        for (name, prefix, url, official) in compatible {
+            let provider = OpenAiCompatible::new(name, prefix, url);
+            self.register(Arc::new(if official {
+                provider.with_json_schema().with_stream_usage()
+            } else {
+                provider
+            }));
+        }
+        self.register(Arc::new(AzureOpenAi::default()));
+        self.register(Arc::new(Anthropic));
+        self.register(Arc::new(Ollama));
+        self.register(Arc::new(OpenAiCompatible::local(
+            "llama.cpp",
+            urls::LLAMACPP_URL,
+        )));
+    }
+
+    /// Appends a provider; it takes effect after the ones already registered.
+    pub fn register(&mut self, provider: Arc<dyn Provider>) {
+        self.providers.push(provider);
+    }
+
+    /// Returns all registered providers in lookup order.
+    pub fn providers(&self) -> &[Arc<dyn Provider>] {
+        &self.providers
+    }
+
+    /// Returns the first provider serving the given model by default.
+    pub fn find_by_model(&self, model_name: &str) -> Option<Arc<dyn Provider>> {
+        self.providers
+            .iter()
+            .find(|p| p.matches_model(model_name))
+            .cloned()
+    }
+
+    /// Resolves the provider and the endpoint for a model and an optionally configured url.
+    ///
+    /// A configured url wins over the model's default endpoint. Its wire format is taken from
+    /// the provider claiming the url and falls back to the OpenAI-compatible protocol, so
+    /// proxies keep working whatever model they serve.
+    ///
+    /// # Returns
+    ///
+    /// * `Some((provider, url))` if an endpoint could be determined.
+    /// * `None` if no url is configured and the model is not recognized.
+    pub fn resolve(&self, model_name: &str, url: &str) -> Option<(Arc<dyn Provider>, String)> {
+        if url.starts_with("http") {
+            let provider = self
+                .providers
+                .iter()
+                .find(|p| p.matches_url(url))
+                .cloned()
+                .unwrap_or_else(|| Arc::new(OpenAiCompatible::generic()));
+            return Some((provider, url.to_string()));
+        }
+        let provider = self.find_by_model(model_name)?;
+        let url = provider.default_url()?;
+        Some((provider, url))
+    }
+}
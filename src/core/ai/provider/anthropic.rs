@@ -0,0 +1,283 @@
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::config::urls;
+use crate::core::ai::error::AiError;
+use crate::core::ai::provider::{Provider, StreamEvent};
+use crate::core::ai::types::{Message, Usage};
+
+/// The `anthropic-version` header value sent with every Messages API request.
+pub const API_VERSION: &str = "2023-06-01";
+
+/// Upper bound of generated tokens; the Messages API requires this field.
+const MAX_TOKENS: u32 = 4096;
+
+#[derive(Serialize, Debug)]
+pub struct Request {
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    pub messages: Vec<Message>,
+    pub max_tokens: u32,
+    pub temperature: f32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Response {
+    pub content: Vec<ContentBlock>,
+    pub stop_reason: Option<String>,
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TokenUsage {
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+}
+
+impl From<TokenUsage> for Usage {
+    fn from(usage: TokenUsage) -> Self {
+        Usage {
+            prompt_tokens: usage.input_tokens,
+            completion_tokens: usage.output_tokens,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ContentBlock {
+    /// Missing in the `delta` of `message_delta` events, which carries the stop reason instead.
+    #[serde(rename = "type", default)]
+    pub block_type: String,
+    pub text: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct StreamEventBody {
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub delta: Option<ContentBlock>,
+    pub error: Option<ErrorDetail>,
+    /// The message of a `message_start` event, carrying the input token count.
+    pub message: Option<StreamMessage>,
+    /// The usage of a `message_delta` event, carrying the output token count.
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct StreamMessage {
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ErrorResponse {
+    pub error: ErrorDetail,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ErrorDetail {
+    #[serde(rename = "type")]
+    pub error_type: String,
+    pub message: String,
+}
+
+/// The native Anthropic Messages API: top-level `system` field, `x-api-key` authentication
+/// and `content[]` blocks in the answer.
+pub struct Anthropic;
+
+impl Provider for Anthropic {
+    fn name(&self) -> &str {
+        "anthropic"
+    }
+
+    fn matches_model(&self, model_name: &str) -> bool {
+        model_name.starts_with("claude")
+    }
+
+    fn model_pattern(&self) -> Option<String> {
+        Some("claude*".to_string())
+    }
+
+    fn matches_url(&self, url: &str) -> bool {
+        url.contains("api.anthropic.com") || url.trim_end_matches('/').ends_with("/v1/messages")
+    }
+
+    fn default_url(&self) -> Option<String> {
+        Some(urls::ANTHROPIC_URL.to_string())
+    }
+
+    fn build_request(
+        &self,
+        model_name: &str,
+        system: Option<&str>,
+        messages: &[Message],
+    ) -> serde_json::Value {
+        let request_body = Request {
+            model: model_name.to_string(),
+            system: system.map(|s| s.to_string()),
+            messages: messages.to_vec(),
+            max_tokens: MAX_TOKENS,
+            temperature: 0.3,
+        };
+        serde_json::to_value(request_body).unwrap_or_default()
+    }
+
+    fn auth_headers(&self, api_key: &str) -> Vec<(String, String)> {
+        vec![
+            ("x-api-key".to_string(), api_key.to_string()),
+            ("anthropic-version".to_string(), API_VERSION.to_string()),
+        ]
+    }
+
+    fn parse_response(&self, body: &str) -> Result<String, AiError> {
+        let res: Response = serde_json::from_str(body)?;
+        let text: String = res
+            .content
+            .iter()
+            .filter(|block| block.block_type == "text")
+            .filter_map(|block| block.text.as_deref())
+            .collect();
+        if text.is_empty() {
+            return Err(AiError::BadResponse(format!(
+                "ai response contains no text (stop_reason: {})",
+                res.stop_reason.as_deref().unwrap_or("unknown")
+            )));
+        }
+        Ok(text)
+    }
+
+    fn parse_usage(&self, body: &str) -> Option<Usage> {
+        serde_json::from_str::<Response>(body)
+            .ok()
+            .and_then(|res| res.usage)
+            .map(Usage::from)
+    }
+
+    fn parse_error(&self, body: &str) -> Option<String> {
+        serde_json::from_str::<ErrorResponse>(body)
+            .ok()
+            .map(|res| format!("{}: {}", res.error.error_type, res.error.message))
+    }
+
+    fn enable_streaming(&self, request_body: &mut serde_json::Value) -> bool {
+        request_body["stream"] = serde_json::Value::Bool(true);
+        true
+    }
+
+    fn parse_stream_event(&self, data: &str) -> Result<StreamEvent, AiError> {
+        let event: StreamEventBody = serde_json::from_str(data)?;
+        match event.event_type.as_str() {
+            "content_block_delta" => Ok(event
+                .delta
+                .and_then(|delta| delta.text)
+                .map_or(StreamEvent::Skip, StreamEvent::Delta)),
+            "message_start" => Ok(event
+                .message
+                .and_then(|message| message.usage)
+                .map_or(StreamEvent::Skip, |usage| StreamEvent::Usage(usage.into()))),
+            "message_delta" => Ok(event
+                .usage
+                .map_or(StreamEvent::Skip, |usage| StreamEvent::Usage(usage.into()))),
+            "message_stop" => Ok(StreamEvent::Done),
+            "error" => Err(match event.error {
+                Some(error) => AiError::from_status(
+                    error_status(&error.error_type),
+                    format!("{}: {}", error.error_type, error.message),
+                ),
+                None => AiError::BadResponse("ai stream failed".to_string()),
+            }),
+            _ => Ok(StreamEvent::Skip),
+        }
+    }
+}
+
+/// Maps an Anthropic error type to the HTTP status it is documented with.
+fn error_status(error_type: &str) -> StatusCode {
+    match error_type {
+        "invalid_request_error" => StatusCode::BAD_REQUEST,
+        "authentication_error" => StatusCode::UNAUTHORIZED,
+        "permission_error" => StatusCode::FORBIDDEN,
+        "not_found_error" => StatusCode::NOT_FOUND,
+        "rate_limit_error" => StatusCode::TOO_MANY_REQUESTS,
+        "overloaded_error" => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_stream_event_text_deltas() {
+        let event = Anthropic
+            .parse_stream_event(
+                r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"fix: "}}"#,
+            )
+            .unwrap();
+        assert!(matches!(event, StreamEvent::Delta(text) if text == "fix: "));
+        assert!(matches!(
+            Anthropic.parse_stream_event(r#"{"type":"ping"}"#).unwrap(),
+            StreamEvent::Skip
+        ));
+        assert!(matches!(
+            Anthropic
+                .parse_stream_event(r#"{"type":"message_stop"}"#)
+                .unwrap(),
+            StreamEvent::Done
+        ));
+    }
+
+    #[test]
+    fn parse_stream_event_usage_in_start_and_delta() {
+        let start = Anthropic
+            .parse_stream_event(
+                r#"{"type":"message_start","message":{"id":"msg_1","usage":{"input_tokens":25,"output_tokens":1}}}"#,
+            )
+            .unwrap();
+        assert!(matches!(
+            start,
+            StreamEvent::Usage(Usage {
+                prompt_tokens: 25,
+                completion_tokens: 1
+            })
+        ));
+        // The final count carries only the output tokens
+        let delta = Anthropic
+            .parse_stream_event(
+                r#"{"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":15}}"#,
+            )
+            .unwrap();
+        assert!(matches!(
+            delta,
+            StreamEvent::Usage(Usage {
+                prompt_tokens: 0,
+                completion_tokens: 15
+            })
+        ));
+    }
+
+    #[test]
+    fn parse_stream_event_errors_keep_their_status() {
+        let error = Anthropic
+            .parse_stream_event(
+                r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+            )
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            AiError::Http {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                ..
+            }
+        ));
+        let error = Anthropic
+            .parse_stream_event(
+                r#"{"type":"error","error":{"type":"rate_limit_error","message":"Slow down"}}"#,
+            )
+            .unwrap_err();
+        assert!(matches!(error, AiError::RateLimited { .. }));
+    }
+}
@@ -0,0 +1,146 @@
+use serde::Deserialize;
+
+use crate::config::urls;
+use crate::core::ai::error::AiError;
+use crate::core::ai::provider::openai::OpenAiCompatible;
+use crate::core::ai::provider::{AuthStyle, Provider, StreamEvent};
+use crate::core::ai::types::{Message, Usage};
+
+/// The `[ai.azure]` table of the config file, describing one Azure OpenAI deployment.
+#[derive(Deserialize, Debug, Clone)]
+pub struct AzureConfig {
+    /// Resource name, or the full endpoint such as `https://my-resource.openai.azure.com`.
+    pub resource: String,
+    pub deployment: String,
+    #[serde(default = "default_api_version")]
+    pub api_version: String,
+}
+
+fn default_api_version() -> String {
+    urls::AZURE_API_VERSION.to_string()
+}
+
+impl AzureConfig {
+    /// Returns the deployment-scoped chat completions url.
+    pub fn url(&self) -> String {
+        let endpoint = if self.resource.contains("://") {
+            self.resource.trim_end_matches('/').to_string()
+        } else {
+            format!("https://{}{}", self.resource, urls::AZURE_HOST_SUFFIX)
+        };
+        format!(
+            "{}/openai/deployments/{}/chat/completions?api-version={}",
+            endpoint, self.deployment, self.api_version
+        )
+    }
+}
+
+/// Name of the Azure provider, selected whenever an `[ai.azure]` table is configured.
+pub const NAME: &str = "azure-openai";
+
+/// Azure OpenAI: the OpenAI protocol on deployment-scoped urls, authenticated by an `api-key` header.
+pub struct AzureOpenAi {
+    protocol: OpenAiCompatible,
+}
+
+impl Default for AzureOpenAi {
+    fn default() -> Self {
+        Self::for_api_version(urls::AZURE_API_VERSION)
+    }
+}
+
+impl AzureOpenAi {
+    /// Creates the provider for the deployment at `url`, following the `api-version` it asks for.
+    pub fn for_url(url: &str) -> Self {
+        let api_version = reqwest::Url::parse(url).ok().and_then(|url| {
+            url.query_pairs()
+                .find(|(key, _)| key == "api-version")
+                .map(|(_, value)| value.into_owned())
+        });
+        Self::for_api_version(api_version.as_deref().unwrap_or(urls::AZURE_API_VERSION))
+    }
+
+    /// Creates the provider for an API version; older versions reject `stream_options`.
+    fn for_api_version(api_version: &str) -> Self {
+        let protocol = OpenAiCompatible::generic().with_json_schema();
+        let stream_usage = api_version
+            .get(..10)
+            .is_some_and(|date| date >= urls::AZURE_STREAM_USAGE_API_VERSION);
+        Self {
+            protocol: if stream_usage {
+                protocol.with_stream_usage()
+            } else {
+                protocol
+            },
+        }
+    }
+}
+
+impl Provider for AzureOpenAi {
+    fn name(&self) -> &str {
+        NAME
+    }
+
+    fn matches_model(&self, _model_name: &str) -> bool {
+        // Deployments carry user-chosen names, Azure is selected by url or an [ai.azure] table
+        false
+    }
+
+    fn model_pattern(&self) -> Option<String> {
+        None
+    }
+
+    fn matches_url(&self, url: &str) -> bool {
+        reqwest::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_string()))
+            .is_some_and(|host| host.ends_with(urls::AZURE_HOST_SUFFIX))
+    }
+
+    fn default_url(&self) -> Option<String> {
+        None
+    }
+
+    fn build_request(
+        &self,
+        model_name: &str,
+        system: Option<&str>,
+        messages: &[Message],
+    ) -> serde_json::Value {
+        // The deployment in the url selects the model, the field is ignored by Azure
+        self.protocol.build_request(model_name, system, messages)
+    }
+
+    fn auth_headers(&self, api_key: &str) -> Vec<(String, String)> {
+        AuthStyle::ApiKey.headers(api_key)
+    }
+
+    fn parse_response(&self, body: &str) -> Result<String, AiError> {
+        self.protocol.parse_response(body)
+    }
+
+    fn parse_usage(&self, body: &str) -> Option<Usage> {
+        self.protocol.parse_usage(body)
+    }
+
+    fn parse_error(&self, body: &str) -> Option<String> {
+        self.protocol.parse_error(body)
+    }
+
+    fn enable_streaming(&self, request_body: &mut serde_json::Value) -> bool {
+        self.protocol.enable_streaming(request_body)
+    }
+
+    fn enable_json_schema(
+        &self,
+        request_body: &mut serde_json::Value,
+        name: &str,
+        schema: &serde_json::Value,
+    ) -> bool {
+        self.protocol.enable_json_schema(request_body, name, schema)
+    }
+
+    fn parse_stream_event(&self, data: &str) -> Result<StreamEvent, AiError> {
+        self.protocol.parse_stream_event(data)
+    }
+}
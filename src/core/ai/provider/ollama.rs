@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::urls;
+use crate::core::ai::error::AiError;
+use crate::core::ai::provider::Provider;
+use crate::core::ai::types::{Message, Usage};
+
+#[derive(Serialize, Debug)]
+pub struct Request {
+    pub model: String,
+    pub messages: Vec<Message>,
+    pub stream: bool,
+    pub options: RequestOptions,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RequestOptions {
+    pub temperature: f32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Response {
+    pub message: Option<Message>,
+    pub error: Option<String>,
+    pub prompt_eval_count: Option<u64>,
+    pub eval_count: Option<u64>,
+}
+
+/// A local Ollama server speaking its native `/api/chat` protocol, no API key needed.
+pub struct Ollama;
+
+impl Provider for Ollama {
+    fn name(&self) -> &str {
+        "ollama"
+    }
+
+    fn matches_model(&self, _model_name: &str) -> bool {
+        // Ollama serves arbitrary model names, it is selected by url only
+        false
+    }
+
+    fn model_pattern(&self) -> Option<String> {
+        None
+    }
+
+    fn matches_url(&self, url: &str) -> bool {
+        url.trim_end_matches('/').ends_with("/api/chat")
+    }
+
+    fn default_url(&self) -> Option<String> {
+        Some(urls::OLLAMA_URL.to_string())
+    }
+
+    fn requires_api_key(&self) -> bool {
+        false
+    }
+
+    fn build_request(
+        &self,
+        model_name: &str,
+        system: Option<&str>,
+        turns: &[Message],
+    ) -> serde_json::Value {
+        let mut messages = Vec::new();
+        if let Some(system) = system {
+            messages.push(Message {
+                role: "system".to_string(),
+                content: system.to_string(),
+            });
+        }
+        messages.extend_from_slice(turns);
+        let request_body = Request {
+            model: model_name.to_string(),
+            messages,
+            stream: false,
+            options: RequestOptions { temperature: 0.3 },
+        };
+        serde_json::to_value(request_body).unwrap_or_default()
+    }
+
+    fn auth_headers(&self, api_key: &str) -> Vec<(String, String)> {
+        // A reverse proxy in front of Ollama may still want a key
+        if api_key.is_empty() {
+            Vec::new()
+        } else {
+            vec![("Authorization".to_string(), format!("Bearer {}", api_key))]
+        }
+    }
+
+    fn parse_response(&self, body: &str) -> Result<String, AiError> {
+        let res: Response = serde_json::from_str(body)?;
+        if let Some(message) = res.message {
+            return Ok(message.content);
+        }
+        Err(AiError::BadResponse(res.error.unwrap_or_else(|| {
+            "ai response contains neither message nor error".to_string()
+        })))
+    }
+
+    fn parse_usage(&self, body: &str) -> Option<Usage> {
+        let res: Response = serde_json::from_str(body).ok()?;
+        if res.prompt_eval_count.is_none() && res.eval_count.is_none() {
+            return None;
+        }
+        Some(Usage {
+            prompt_tokens: res.prompt_eval_count.unwrap_or_default(),
+            completion_tokens: res.eval_count.unwrap_or_default(),
+        })
+    }
+
+    fn enable_json_schema(
+        &self,
+        request_body: &mut serde_json::Value,
+        _name: &str,
+        schema: &serde_json::Value,
+    ) -> bool {
+        // Ollama takes the schema itself as the output format
+        request_body["format"] = schema.clone();
+        true
+    }
+
+    fn parse_error(&self, body: &str) -> Option<String> {
+        serde_json::from_str::<Response>(body)
+            .ok()
+            .and_then(|res| res.error)
+    }
+}
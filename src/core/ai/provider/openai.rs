@@ -0,0 +1,384 @@
+use serde::Deserialize;
+
+use crate::core::ai::error::AiError;
+use crate::core::ai::provider::{AuthStyle, Provider, StreamEvent};
+use crate::core::ai::transport::Transport;
+use crate::core::ai::types::{Message, Request, Response, StreamChunk, Usage};
+
+/// A `[[providers]]` entry of the config file.
+#[derive(Deserialize, Debug)]
+pub struct ProviderConfig {
+    pub name: String,
+    /// Model prefix, or a glob when it contains `*` or `?`.
+    pub model: String,
+    pub url: String,
+    #[serde(default)]
+    pub auth: AuthStyle,
+    /// Fields merged into every request body.
+    #[serde(default)]
+    pub extra_body: Option<toml::Table>,
+    /// Whether the gateway accepts a strict JSON schema as `response_format`.
+    #[serde(default)]
+    pub json_schema: bool,
+    /// Whether the gateway accepts `stream_options` asking for the usage of streamed answers.
+    #[serde(default)]
+    pub stream_usage: bool,
+    /// Proxy, root certificate and extra headers for this provider.
+    #[serde(flatten)]
+    pub transport: Transport,
+}
+
+/// A vendor speaking the OpenAI `/chat/completions` protocol.
+pub struct OpenAiCompatible {
+    name: String,
+    model_pattern: Option<String>,
+    url: Option<String>,
+    auth: AuthStyle,
+    extra_body: serde_json::Map<String, serde_json::Value>,
+    transport: Transport,
+    /// Serves any loopback url and needs no API key, e.g. a llama.cpp server.
+    local: bool,
+    /// Sends the JSON schema of structured answers, which many compatible vendors reject.
+    json_schema: bool,
+    /// Asks streamed answers to end with a usage chunk, which many compatible vendors reject.
+    stream_usage: bool,
+}
+
+impl OpenAiCompatible {
+    /// Creates a provider serving models starting with `model_prefix` at `url`.
+    pub fn new(name: &str, model_prefix: &str, url: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            model_pattern: Some(model_prefix.to_string()),
+            url: Some(url.to_string()),
+            auth: AuthStyle::Bearer,
+            extra_body: serde_json::Map::new(),
+            transport: Transport::default(),
+            local: false,
+            json_schema: false,
+            stream_usage: false,
+        }
+    }
+
+    /// Creates the fallback provider used for configured urls no other provider claims.
+    pub fn generic() -> Self {
+        Self {
+            name: "openai-compatible".to_string(),
+            model_pattern: None,
+            url: None,
+            auth: AuthStyle::Bearer,
+            extra_body: serde_json::Map::new(),
+            transport: Transport::default(),
+            local: false,
+            json_schema: false,
+            stream_usage: false,
+        }
+    }
+
+    /// Creates a provider from a `[[providers]]` entry of the config file.
+    pub fn from_config(config: ProviderConfig) -> Self {
+        let extra_body = config
+            .extra_body
+            .and_then(|table| serde_json::to_value(table).ok())
+            .and_then(|value| value.as_object().cloned())
+            .unwrap_or_default();
+        Self {
+            name: config.name,
+            model_pattern: Some(config.model),
+            url: Some(config.url),
+            auth: config.auth,
+            extra_body,
+            transport: config.transport,
+            local: false,
+            json_schema: config.json_schema,
+            stream_usage: config.stream_usage,
+        }
+    }
+
+    /// Sends the JSON schema of structured answers as `response_format`.
+    pub fn with_json_schema(mut self) -> Self {
+        self.json_schema = true;
+        self
+    }
+
+    /// Asks streamed answers to report their usage through `stream_options`.
+    pub fn with_stream_usage(mut self) -> Self {
+        self.stream_usage = true;
+        self
+    }
+
+    /// Creates a provider for an OpenAI-compatible server on this machine, e.g. llama.cpp.
+    pub fn local(name: &str, url: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            model_pattern: None,
+            url: Some(url.to_string()),
+            auth: AuthStyle::Bearer,
+            extra_body: serde_json::Map::new(),
+            transport: Transport::default(),
+            local: true,
+            json_schema: false,
+            stream_usage: false,
+        }
+    }
+}
+
+impl Provider for OpenAiCompatible {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn matches_model(&self, model_name: &str) -> bool {
+        self.model_pattern
+            .as_deref()
+            .is_some_and(|pattern| matches_pattern(pattern, model_name))
+    }
+
+    fn model_pattern(&self) -> Option<String> {
+        self.model_pattern.as_ref().map(|pattern| {
+            if pattern.contains(['*', '?']) {
+                pattern.clone()
+            } else {
+                format!("{}*", pattern)
+            }
+        })
+    }
+
+    fn matches_url(&self, url: &str) -> bool {
+        if self.local {
+            return is_loopback(url);
+        }
+        self.url
+            .as_deref()
+            .is_some_and(|own| url.starts_with(own.trim_end_matches('/')))
+    }
+
+    fn default_url(&self) -> Option<String> {
+        self.url.clone()
+    }
+
+    fn requires_api_key(&self) -> bool {
+        !self.local && self.auth != AuthStyle::None
+    }
+
+    fn build_request(
+        &self,
+        model_name: &str,
+        system: Option<&str>,
+        messages: &[Message],
+    ) -> serde_json::Value {
+        let mut request_body = Request {
+            model: model_name.to_string(),
+            ..Default::default()
+        };
+        if let Some(system) = system {
+            request_body.messages.push(Message {
+                role: "system".to_string(),
+                content: system.to_string(),
+            });
+        }
+        request_body.messages.extend_from_slice(messages);
+        let mut value = serde_json::to_value(request_body).unwrap_or_default();
+        if let Some(body) = value.as_object_mut() {
+            for (key, extra) in &self.extra_body {
+                body.insert(key.clone(), extra.clone());
+            }
+        }
+        value
+    }
+
+    fn transport(&self) -> Transport {
+        self.transport.clone()
+    }
+
+    fn auth_headers(&self, api_key: &str) -> Vec<(String, String)> {
+        if api_key.is_empty() && !self.requires_api_key() {
+            return Vec::new();
+        }
+        self.auth.headers(api_key)
+    }
+
+    fn parse_response(&self, body: &str) -> Result<String, AiError> {
+        let res: Response = serde_json::from_str(body)?;
+
+        if let Some(res) = res.choices {
+            if let Some(choice) = res.into_iter().next() {
+                return Ok(choice.message.content);
+            }
+            return Err(AiError::BadResponse(
+                "ai response contains no choices".to_string(),
+            ));
+        }
+        if let Some(res) = res.error {
+            return Err(AiError::BadResponse(res.message));
+        }
+        Err(AiError::BadResponse(
+            "ai response contains neither choices nor error".to_string(),
+        ))
+    }
+
+    fn parse_usage(&self, body: &str) -> Option<Usage> {
+        serde_json::from_str::<Response>(body)
+            .ok()
+            .and_then(|res| res.usage)
+    }
+
+    fn parse_error(&self, body: &str) -> Option<String> {
+        serde_json::from_str::<Response>(body)
+            .ok()
+            .and_then(|res| res.error)
+            .map(|error| error.message)
+    }
+
+    fn enable_streaming(&self, request_body: &mut serde_json::Value) -> bool {
+        request_body["stream"] = serde_json::Value::Bool(true);
+        if self.stream_usage {
+            // Without it OpenAI and Azure send no usage chunk, and streamed requests go unrecorded
+            request_body["stream_options"] = serde_json::json!({ "include_usage": true });
+        }
+        true
+    }
+
+    fn enable_json_schema(
+        &self,
+        request_body: &mut serde_json::Value,
+        name: &str,
+        schema: &serde_json::Value,
+    ) -> bool {
+        if !self.json_schema {
+            return false;
+        }
+        request_body["response_format"] = serde_json::json!({
+            "type": "json_schema",
+            "json_schema": { "name": name, "schema": schema, "strict": true },
+        });
+        true
+    }
+
+    fn parse_stream_event(&self, data: &str) -> Result<StreamEvent, AiError> {
+        if data == "[DONE]" {
+            return Ok(StreamEvent::Done);
+        }
+        let chunk: StreamChunk = serde_json::from_str(data)?;
+        if let Some(error) = chunk.error {
+            return Err(AiError::BadResponse(error.message));
+        }
+        let content = chunk
+            .choices
+            .and_then(|choices| choices.into_iter().next())
+            .and_then(|choice| choice.delta.content);
+        // The usage arrives in a final chunk with no choices
+        Ok(match (content, chunk.usage) {
+            (Some(text), _) if !text.is_empty() => StreamEvent::Delta(text),
+            (_, Some(usage)) => StreamEvent::Usage(usage),
+            _ => StreamEvent::Skip,
+        })
+    }
+}
+
+/// Matches a model name against a prefix, or a glob with `*` and `?` wildcards.
+pub fn matches_pattern(pattern: &str, model_name: &str) -> bool {
+    if !pattern.contains(['*', '?']) {
+        return model_name.starts_with(pattern);
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = model_name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Returns true if the url points to this machine.
+fn is_loopback(url: &str) -> bool {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+        .is_some_and(|host| host == "localhost" || host == "[::1]" || host.starts_with("127."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_prefix_without_wildcards() {
+        assert!(matches_pattern("gpt", "gpt-4o"));
+        assert!(!matches_pattern("gpt", "chatgpt-4o"));
+    }
+
+    #[test]
+    fn matches_glob_with_backtracking() {
+        assert!(matches_pattern("gpt-*-mini", "gpt-4o-mini"));
+        // The first `-mini` candidate is a dead end, the star has to take more
+        assert!(matches_pattern("gpt-*-mini", "gpt-4o-mini-x-mini"));
+        assert!(!matches_pattern("gpt-*-mini", "gpt-4o-mini-2024"));
+        assert!(matches_pattern("*a*b", "xaxab"));
+        assert!(!matches_pattern("*a*b", "xaxa"));
+        assert!(matches_pattern("corp-*", "corp-"));
+        assert!(matches_pattern("**", ""));
+    }
+
+    #[test]
+    fn matches_glob_single_characters() {
+        assert!(matches_pattern("o?-mini", "o3-mini"));
+        assert!(!matches_pattern("o?-mini", "o-mini"));
+        assert!(!matches_pattern("o?", "o3-mini"));
+    }
+
+    #[test]
+    fn parse_stream_event_delta_usage_and_done() {
+        let provider = OpenAiCompatible::generic();
+        let delta = provider
+            .parse_stream_event(r#"{"choices":[{"delta":{"content":"feat"}}]}"#)
+            .unwrap();
+        assert!(matches!(delta, StreamEvent::Delta(text) if text == "feat"));
+
+        let usage = provider
+            .parse_stream_event(
+                r#"{"choices":[],"usage":{"prompt_tokens":12,"completion_tokens":3}}"#,
+            )
+            .unwrap();
+        assert!(matches!(
+            usage,
+            StreamEvent::Usage(Usage {
+                prompt_tokens: 12,
+                completion_tokens: 3
+            })
+        ));
+
+        let role = provider
+            .parse_stream_event(r#"{"choices":[{"delta":{"role":"assistant","content":""}}]}"#)
+            .unwrap();
+        assert!(matches!(role, StreamEvent::Skip));
+
+        assert!(matches!(
+            provider.parse_stream_event("[DONE]").unwrap(),
+            StreamEvent::Done
+        ));
+    }
+
+    #[test]
+    fn parse_stream_event_errors() {
+        let provider = OpenAiCompatible::generic();
+        let error = provider
+            .parse_stream_event(r#"{"error":{"message":"model overloaded"}}"#)
+            .unwrap_err();
+        assert!(matches!(error, AiError::BadResponse(message) if message == "model overloaded"));
+        assert!(provider.parse_stream_event("not json").is_err());
+    }
+}
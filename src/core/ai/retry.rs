@@ -0,0 +1,142 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+
+/// How failed AI requests are retried.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one. `1` disables retrying.
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled for every further retry.
+    pub base_delay: Duration,
+    /// Upper bound of a single delay, also applied to `Retry-After`.
+    pub max_delay: Duration,
+    /// Whether to randomize delays so that parallel clients do not retry in lockstep.
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the backoff delay before the given retry.
+    ///
+    /// # Arguments
+    ///
+    /// * `retry` - The number of the retry, starting at 1.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        if !self.jitter {
+            return delay;
+        }
+        // Equal jitter: keep half of the delay, randomize the other half
+        let half = delay / 2;
+        let random = RandomState::new().build_hasher().finish();
+        half + Duration::from_millis(random % (half.as_millis() as u64 + 1))
+    }
+
+    /// Returns the delay requested by a `Retry-After` header, capped at `max_delay`.
+    ///
+    /// # Arguments
+    ///
+    /// * `headers` - The headers of the failed response.
+    pub fn retry_after(&self, headers: &HeaderMap) -> Option<Duration> {
+        let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+        let delay = if let Ok(seconds) = value.parse::<u64>() {
+            Duration::from_secs(seconds)
+        } else {
+            let date = DateTime::parse_from_rfc2822(value).ok()?;
+            (date.with_timezone(&Utc) - Utc::now())
+                .to_std()
+                .unwrap_or_default()
+        };
+        Some(delay.min(self.max_delay))
+    }
+}
+
+/// Returns true if a response with this status may succeed when sent again.
+///
+/// Rate limits, timeouts and server-side failures are transient; authentication and
+/// malformed requests fail the same way every time.
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(
+        status.as_u16(),
+        408 | 425 | 429 | 500 | 502 | 503 | 504 | 529
+    )
+}
+
+/// Returns true if a transport error may succeed when the request is sent again.
+pub fn is_retryable_error(error: &reqwest::Error) -> bool {
+    error.is_timeout() || error.is_connect() || error.is_request()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(retry_after: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(retry_after).unwrap());
+        headers
+    }
+
+    #[test]
+    fn retry_after_seconds_capped_at_max_delay() {
+        let policy = RetryPolicy::default();
+        assert_eq!(
+            policy.retry_after(&headers("7")),
+            Some(Duration::from_secs(7))
+        );
+        assert_eq!(policy.retry_after(&headers("3600")), Some(policy.max_delay));
+    }
+
+    /// Formats a time as an HTTP date, e.g. `Wed, 21 Oct 2015 07:28:00 GMT`.
+    fn http_date(time: DateTime<Utc>) -> String {
+        time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+    }
+
+    #[test]
+    fn retry_after_http_date() {
+        let policy = RetryPolicy::default();
+        let date = http_date(Utc::now() + chrono::Duration::seconds(20));
+        let delay = policy.retry_after(&headers(&date)).unwrap();
+        assert!(delay > Duration::from_secs(15) && delay <= Duration::from_secs(20));
+
+        // A date in the past asks for no delay at all
+        let past = http_date(Utc::now() - chrono::Duration::seconds(20));
+        assert_eq!(policy.retry_after(&headers(&past)), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn retry_after_ignores_missing_and_malformed_values() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.retry_after(&HeaderMap::new()), None);
+        assert_eq!(policy.retry_after(&headers("soon")), None);
+        assert_eq!(policy.retry_after(&headers("-5")), None);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max_delay() {
+        let policy = RetryPolicy {
+            jitter: false,
+            ..RetryPolicy::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff(3), Duration::from_secs(2));
+        assert_eq!(policy.backoff(20), policy.max_delay);
+    }
+}
@@ -0,0 +1,71 @@
+/// Estimates the number of tokens a text is split into.
+///
+/// Providers use different tokenizers, so this is a heuristic erring on the high side: about four
+/// characters of ASCII text make one token, while every other character (e.g. CJK) may be a
+/// token of its own.
+///
+/// # Arguments
+///
+/// * `text` - The text to estimate.
+///
+/// # Returns
+///
+/// * The estimated token count.
+pub fn estimate(text: &str) -> u64 {
+    let (ascii, other) = text.chars().fold((0u64, 0u64), |(ascii, other), c| {
+        if c.is_ascii() {
+            (ascii + 1, other)
+        } else {
+            (ascii, other + 1)
+        }
+    });
+    ascii.div_ceil(4) + other
+}
+
+/// Tokens kept free for the answer when fitting a prompt into the context window.
+pub const DEFAULT_RESPONSE_RESERVE: u64 = 1_024;
+
+/// Context windows of well-known models by name prefix; the longest matching prefix wins.
+const CONTEXT_WINDOWS: &[(&str, u64)] = &[
+    ("gpt-3.5", 16_385),
+    ("gpt-4", 8_192),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4o", 128_000),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-5", 400_000),
+    ("o1", 200_000),
+    ("o3", 200_000),
+    ("o4", 200_000),
+    ("claude", 200_000),
+    ("gemini", 1_048_576),
+    ("deepseek", 65_536),
+    ("qwen", 131_072),
+    ("moonshot-v1-8k", 8_192),
+    ("moonshot-v1-32k", 32_768),
+    ("moonshot-v1-128k", 131_072),
+    ("kimi", 131_072),
+    ("glm-4", 128_000),
+    ("doubao", 32_768),
+    ("qianfan", 32_768),
+    ("llama3", 8_192),
+    ("llama3.1", 131_072),
+    ("llama3.2", 131_072),
+    ("llama3.3", 131_072),
+];
+
+/// Returns the context window of a well-known model.
+///
+/// # Arguments
+///
+/// * `model_name` - The name of the AI model.
+///
+/// # Returns
+///
+/// * `Some(tokens)` if the model is in the built-in table, `None` otherwise.
+pub fn context_window(model_name: &str) -> Option<u64> {
+    CONTEXT_WINDOWS
+        .iter()
+        .filter(|(prefix, _)| model_name.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, window)| *window)
+}
@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Certificate, Proxy};
+use serde::Deserialize;
+
+use crate::core::ai::error::AiError;
+
+/// Network settings of an AI endpoint: proxy, private root CA and extra headers.
+///
+/// Read from the ai section, `[[providers]]` and `[[ai.fallbacks]]` entries; the more specific
+/// source wins per setting.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Transport {
+    /// Proxy for all requests, e.g. `http://proxy.corp:3128`. Without it the
+    /// `HTTPS_PROXY` / `HTTP_PROXY` environment variables apply.
+    pub proxy: Option<String>,
+    /// PEM bundle or DER file with root certificates trusted in addition to the system ones.
+    pub ca_cert: Option<PathBuf>,
+    /// Headers sent with every request, replacing authentication headers of the same name.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl Transport {
+    /// Returns these settings with every setting of `other` taking precedence.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The more specific settings.
+    pub fn overridden_by(&self, other: &Transport) -> Transport {
+        let mut headers = self.headers.clone();
+        headers.extend(other.headers.clone());
+        Transport {
+            proxy: other.proxy.clone().or_else(|| self.proxy.clone()),
+            ca_cert: other.ca_cert.clone().or_else(|| self.ca_cert.clone()),
+            headers,
+        }
+    }
+
+    /// Builds an HTTP client using the proxy and root certificates.
+    ///
+    /// # Arguments
+    ///
+    /// * `connect_timeout` - Time allowed to establish a connection.
+    /// * `timeout` - Time allowed for a whole request.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(reqwest::Client)` if the settings are valid.
+    /// * `Err(AiError::ConfigInvalid)` if the proxy url or the certificate file cannot be used.
+    pub fn build_client(
+        &self,
+        connect_timeout: Duration,
+        timeout: Duration,
+    ) -> Result<reqwest::Client, AiError> {
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(connect_timeout)
+            .timeout(timeout);
+        if let Some(proxy) = &self.proxy {
+            let proxy = Proxy::all(proxy).map_err(|e| {
+                AiError::ConfigInvalid(format!("proxy '{}' is not a valid url ({})", proxy, e))
+            })?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(path) = &self.ca_cert {
+            for cert in read_certificates(path)? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        Ok(builder.build()?)
+    }
+
+    /// Returns the extra headers as a header map.
+    ///
+    /// # Returns
+    ///
+    /// * `Err(AiError::ConfigInvalid)` if a header name or value cannot be sent.
+    pub fn header_map(&self) -> Result<HeaderMap, AiError> {
+        let mut map = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let invalid = || AiError::ConfigInvalid(format!("header '{}' cannot be sent", name));
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| invalid())?;
+            let value = HeaderValue::from_str(value).map_err(|_| invalid())?;
+            map.insert(name, value);
+        }
+        Ok(map)
+    }
+}
+
+/// Reads the root certificates of a PEM bundle, or a single DER encoded certificate.
+fn read_certificates(path: &PathBuf) -> Result<Vec<Certificate>, AiError> {
+    let invalid = |e: &dyn std::fmt::Display| {
+        AiError::ConfigInvalid(format!(
+            "ca_cert '{}' cannot be used ({})",
+            path.display(),
+            e
+        ))
+    };
+    let bytes = std::fs::read(path).map_err(|e| invalid(&e))?;
+    let certs = if String::from_utf8_lossy(&bytes).contains("-----BEGIN") {
+        Certificate::from_pem_bundle(&bytes)
+    } else {
+        Certificate::from_der(&bytes).map(|cert| vec![cert])
+    };
+    certs.map_err(|e| invalid(&e))
+}
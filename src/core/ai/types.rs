@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
-#[derive(Serialize, Deserialize, Debug, Validate)]
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
 pub struct Message {
     #[validate(length(min = 1))]
     pub role: String,
@@ -42,6 +42,31 @@ pub struct RequestExtraBody {
 pub struct Response {
     pub choices: Option<Vec<Choice>>,
     pub error: Option<ResponseError>,
+    pub usage: Option<Usage>,
+}
+
+/// Token counts reported by a provider for one request.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+}
+
+impl Usage {
+    /// Returns the number of prompt and completion tokens together.
+    pub fn total(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    /// Combines partial counts of a streamed answer; each count keeps the larger value.
+    pub fn merge(self, other: Usage) -> Usage {
+        Usage {
+            prompt_tokens: self.prompt_tokens.max(other.prompt_tokens),
+            completion_tokens: self.completion_tokens.max(other.completion_tokens),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -56,3 +81,20 @@ pub struct ResponseError {
     #[serde(rename = "type")]
     pub error_type: Option<String>,
 }
+
+#[derive(Deserialize, Debug)]
+pub struct StreamChunk {
+    pub choices: Option<Vec<StreamChoice>>,
+    pub error: Option<ResponseError>,
+    pub usage: Option<Usage>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct StreamChoice {
+    pub delta: Delta,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Delta {
+    pub content: Option<String>,
+}
@@ -0,0 +1,158 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, Local};
+use gim_config::directory;
+use serde::{Deserialize, Serialize};
+
+use crate::config::constants::RESPONSE_CACHE_DIR;
+use crate::utils::output;
+
+/// One cached AI answer, stored as a JSON file named after its key.
+#[derive(Serialize, Deserialize, Debug)]
+struct Record {
+    time: DateTime<Local>,
+    model: String,
+    text: String,
+}
+
+/// Returns the path of the cache directory in the config directory.
+fn cache_dir() -> io::Result<PathBuf> {
+    Ok(directory::config_dir()?.join(RESPONSE_CACHE_DIR))
+}
+
+/// Computes the cache key of a request.
+///
+/// The key is a 64-bit FNV-1a hash of the fields, each prefixed with its length so that
+/// different splits of the same text do not collide. Unlike `DefaultHasher` it is stable across
+/// Rust versions, so the cache survives an upgrade of gim.
+///
+/// # Arguments
+///
+/// * `url` - The endpoint url, so providers serving the same model name do not share answers.
+/// * `model` - The name of the AI model.
+/// * `system` - The system prompt.
+/// * `user` - The user content.
+///
+/// # Returns
+///
+/// * The key as 16 hex digits.
+pub fn key(url: &str, model: &str, system: &str, user: &str) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for field in [url, model, system, user] {
+        let len = (field.len() as u64).to_le_bytes();
+        for byte in len.iter().chain(field.as_bytes()) {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+/// Looks up a cached answer.
+///
+/// # Arguments
+///
+/// * `key` - The cache key of the request.
+/// * `ttl` - How long answers stay valid; expired answers are removed.
+///
+/// # Returns
+///
+/// * `Some(answer)` if a valid answer is cached, `None` otherwise.
+pub fn get(key: &str, ttl: Duration) -> Option<String> {
+    let path = cache_dir().ok()?.join(format!("{}.json", key));
+    let content = fs::read_to_string(&path).ok()?;
+    match serde_json::from_str::<Record>(&content) {
+        Ok(record) if record.time + ttl > Local::now() => Some(record.text),
+        Ok(_) => {
+            let _ = fs::remove_file(&path);
+            None
+        }
+        Err(e) => {
+            output::print_verbose(&format!(
+                "Skip unreadable cache file {}: {}",
+                path.display(),
+                e
+            ));
+            None
+        }
+    }
+}
+
+/// Stores an answer and evicts the oldest answers beyond the size limit.
+///
+/// # Arguments
+///
+/// * `key` - The cache key of the request.
+/// * `model` - The name of the AI model that answered.
+/// * `text` - The answer.
+/// * `max_bytes` - The size limit of the whole cache.
+pub fn put(key: &str, model: &str, text: &str, max_bytes: u64) -> io::Result<()> {
+    let dir = cache_dir()?;
+    fs::create_dir_all(&dir)?;
+    let record = Record {
+        time: Local::now(),
+        model: model.to_string(),
+        text: text.to_string(),
+    };
+    fs::write(
+        dir.join(format!("{}.json", key)),
+        serde_json::to_string(&record)?,
+    )?;
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        // Concurrent requests evict from the same directory, files may vanish meanwhile
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        if metadata.is_file() {
+            files.push((metadata.modified()?, metadata.len(), entry.path()));
+        }
+    }
+    // Newest first, everything past the limit goes
+    files.sort_by_key(|file| std::cmp::Reverse(file.0));
+    let mut size = 0;
+    for (_, len, path) in files {
+        size += len;
+        if size > max_bytes {
+            remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes a cache file, ignoring a file another request removed already.
+fn remove_file(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Removes every cached answer.
+///
+/// # Returns
+///
+/// * `Ok((count, bytes))` with the number and total size of the removed answers.
+pub fn clear() -> io::Result<(usize, u64)> {
+    let dir = cache_dir()?;
+    if !dir.exists() {
+        return Ok((0, 0));
+    }
+    let (mut count, mut bytes) = (0, 0);
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_file() {
+            remove_file(&entry.path())?;
+            count += 1;
+            bytes += metadata.len();
+        }
+    }
+    Ok((count, bytes))
+}
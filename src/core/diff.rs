@@ -1,9 +1,12 @@
+use crate::core::ai::tokens;
 use crate::core::git;
-use crate::utils::output;
+use crate::utils::{output, report};
+use serde::Serialize;
 use std::collections::HashSet;
 
 /// File type classification for prioritization.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 enum FileType {
     Code,   // Programming language files
     Config, // Configuration files (xml, toml, yaml, json, etc.)
@@ -12,16 +15,40 @@ enum FileType {
 }
 
 /// Represents a changed file with metadata.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 struct FileChange {
     path: String,
     lines_changed: usize,
+    #[serde(rename = "type")]
     file_type: FileType,
 }
 
+/// A changed file in the JSON report, with whether it was sent to the AI.
+#[derive(Serialize)]
+struct FileReport<'a> {
+    #[serde(flatten)]
+    change: &'a FileChange,
+    selected: bool,
+}
+
+/// Records the changed files and which of them were selected in the JSON report.
+fn report_files(key: &str, changes: &[FileChange], selected: &[String]) {
+    if !report::is_enabled() {
+        return;
+    }
+    let files: Vec<FileReport> = changes
+        .iter()
+        .map(|change| FileReport {
+            change,
+            selected: selected.contains(&change.path),
+        })
+        .collect();
+    report::set(key, files);
+}
+
 /// Classifies a file based on its extension.
 fn classify_file_type(path: &str) -> FileType {
-    let extension = path.split('.').last().unwrap_or("").to_lowercase();
+    let extension = path.split('.').next_back().unwrap_or("").to_lowercase();
 
     match extension.as_str() {
         // Code files
@@ -112,7 +139,7 @@ fn select_files(changes: Vec<FileChange>, max_files: usize) -> Vec<String> {
     };
 
     // Sort by lines_changed descending to identify most significant changes
-    filtered_changes.sort_by(|a, b| b.lines_changed.cmp(&a.lines_changed));
+    filtered_changes.sort_by_key(|c| std::cmp::Reverse(c.lines_changed));
 
     // Take top N files by lines changed if max_files is set
     if max_files > 0 {
@@ -160,10 +187,11 @@ pub fn build_staging_diff(selected_files: Option<&[String]>) -> String {
         for line in name_status.lines() {
             if let Some((status, filename)) = line.split_once('\t') {
                 // Skip if not in selected files
-                if let Some(ref set) = selected_set {
-                    if !set.contains(filename) && status != "D" {
-                        continue;
-                    }
+                if let Some(ref set) = selected_set
+                    && !set.contains(filename)
+                    && status != "D"
+                {
+                    continue;
                 }
 
                 if status == "D" {
@@ -177,7 +205,7 @@ pub fn build_staging_diff(selected_files: Option<&[String]>) -> String {
                 diff_content.push('\n');
             }
         }
-        diff_content.push_str("\n");
+        diff_content.push('\n');
 
         // Add full diff content only for added/modified files
         if !full_diff.is_empty() {
@@ -185,7 +213,7 @@ pub fn build_staging_diff(selected_files: Option<&[String]>) -> String {
                 "\nDetailed changes for added/modified files (excluding deleted files):\n",
             );
             diff_content.push_str(&full_diff);
-            diff_content.push_str("\n");
+            diff_content.push('\n');
         }
     }
 
@@ -225,10 +253,11 @@ pub fn build_last_commit_diff(selected_files: Option<&[String]>) -> String {
     for line in name_status.lines() {
         if let Some((status, filename)) = line.split_once('\t') {
             // Skip if not in selected files
-            if let Some(ref set) = selected_set {
-                if !set.contains(filename) && status != "D" {
-                    continue;
-                }
+            if let Some(ref set) = selected_set
+                && !set.contains(filename)
+                && status != "D"
+            {
+                continue;
             }
 
             if status == "D" {
@@ -242,13 +271,13 @@ pub fn build_last_commit_diff(selected_files: Option<&[String]>) -> String {
             diff_content.push('\n');
         }
     }
-    diff_content.push_str("\n");
+    diff_content.push('\n');
 
     // Append detailed diff only for added/modified files
     if !full_diff.is_empty() {
         diff_content.push_str("\nDetailed changes for added/modified files in last commit (excluding deleted files):\n");
         diff_content.push_str(&full_diff);
-        diff_content.push_str("\n");
+        diff_content.push('\n');
     }
 
     output::print_normal("As '-p' option is enabled, I will amend the last commit message");
@@ -278,17 +307,17 @@ pub fn build_diff_content(
 
     if !changes.is_empty() {
         output::print_normal(&format!("Found {} changes:", changes.len()));
+        let mut statuses = Vec::new();
         for entry in changes.iter() {
+            let ignored = !auto_add && (entry.starts_with(' ') || entry.starts_with('?'));
             output::print_normal(&format!(
                 "{:?} {}",
                 entry,
-                if !auto_add && (entry.starts_with(' ') || entry.starts_with('?')) {
-                    " - <<Ignored>>"
-                } else {
-                    ""
-                }
+                if ignored { " - <<Ignored>>" } else { "" }
             ));
+            statuses.push(serde_json::json!({ "status": entry, "ignored": ignored }));
         }
+        report::set("changes", statuses);
 
         // Auto add changes if enabled
         if auto_add {
@@ -301,7 +330,8 @@ pub fn build_diff_content(
             let name_status = git::get_staged_name_status();
             let file_changes = parse_diff_stats(&numstat, &name_status);
 
-            let selected = select_files(file_changes, max_files);
+            let selected = select_files(file_changes.clone(), max_files);
+            report_files("files", &file_changes, &selected);
             if !selected.is_empty() && selected.len() < changes.len() {
                 output::print_normal(&format!(
                     "Limiting diff to {} most significant files (out of {} total changes)",
@@ -326,7 +356,8 @@ pub fn build_diff_content(
             let name_status = git::get_last_commit_name_status();
             let file_changes = parse_diff_stats(&numstat, &name_status);
 
-            let selected = select_files(file_changes, max_files);
+            let selected = select_files(file_changes.clone(), max_files);
+            report_files("last_commit_files", &file_changes, &selected);
             if !selected.is_empty() {
                 output::print_verbose(&format!(
                     "Limiting last commit diff to {} files",
@@ -343,3 +374,220 @@ pub fn build_diff_content(
 
     diff_content
 }
+
+/// Splits diff content into parts of at most `max_tokens` estimated tokens each.
+///
+/// The content is cut before each `diff --git` file header so files stay whole where possible.
+/// A file too large on its own is cut before its hunks, repeating the file header in every part,
+/// and a hunk too large on its own is cut between lines.
+///
+/// # Arguments
+///
+/// * `diff_content` - The diff content to split.
+/// * `max_tokens` - The token budget of one part.
+///
+/// # Returns
+///
+/// * `Vec<String>` with the parts in order, a single part if the content fits.
+pub fn split_diff(diff_content: &str, max_tokens: u64) -> Vec<String> {
+    pack(split_sections(diff_content, max_tokens), max_tokens)
+}
+
+/// Splits diff content into one part per file, each of at most `max_tokens` estimated tokens.
+///
+/// The summary of the changed files preceding the first file header is kept with the first
+/// file. A file too large on its own is split like [`split_diff`] does.
+///
+/// # Arguments
+///
+/// * `diff_content` - The diff content to split.
+/// * `max_tokens` - The token budget of one part.
+///
+/// # Returns
+///
+/// * `Vec<String>` with the parts in order.
+pub fn split_per_file(diff_content: &str, max_tokens: u64) -> Vec<String> {
+    let mut parts = split_sections(diff_content, max_tokens);
+    if parts.len() > 1 && !parts[0].starts_with("diff --git ") {
+        let preamble = parts.remove(0);
+        if tokens::estimate(&preamble) + tokens::estimate(&parts[0]) <= max_tokens {
+            parts[0].insert_str(0, &preamble);
+        } else {
+            parts.insert(0, preamble);
+        }
+    }
+    parts
+}
+
+/// Returns the number of files in diff content.
+pub fn count_files(diff_content: &str) -> usize {
+    diff_content
+        .lines()
+        .filter(|line| line.starts_with("diff --git "))
+        .count()
+}
+
+/// Splits diff content before every file header, further splitting sections over the budget.
+fn split_sections(diff_content: &str, max_tokens: u64) -> Vec<String> {
+    let mut pieces = Vec::new();
+    for section in split_before(diff_content, |line| line.starts_with("diff --git ")) {
+        if tokens::estimate(&section) <= max_tokens {
+            pieces.push(section);
+            continue;
+        }
+        let (header, body) = match section.find("\n@@") {
+            Some(pos) => section.split_at(pos + 1),
+            None => ("", section.as_str()),
+        };
+        let budget = max_tokens.saturating_sub(tokens::estimate(header)).max(1);
+        for hunk in split_before(body, |line| line.starts_with("@@")) {
+            let lines = if tokens::estimate(&hunk) <= budget {
+                vec![hunk]
+            } else {
+                pack(hunk.split_inclusive('\n').map(str::to_string), budget)
+            };
+            pieces.extend(
+                lines
+                    .into_iter()
+                    .map(|lines| format!("{}{}", header, lines)),
+            );
+        }
+    }
+    pieces
+}
+
+/// Splits text into groups of lines, starting a new group at every boundary line.
+fn split_before(text: &str, is_boundary: impl Fn(&str) -> bool) -> Vec<String> {
+    let mut groups: Vec<String> = Vec::new();
+    for line in text.split_inclusive('\n') {
+        match groups.last_mut() {
+            Some(group) if !is_boundary(line) => group.push_str(line),
+            _ => groups.push(line.to_string()),
+        }
+    }
+    groups
+}
+
+/// Joins consecutive pieces as long as they stay within the token budget.
+fn pack(pieces: impl IntoIterator<Item = String>, max_tokens: u64) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut current_tokens = 0;
+    for piece in pieces {
+        let piece_tokens = tokens::estimate(&piece);
+        if !current.is_empty() && current_tokens + piece_tokens > max_tokens {
+            parts.push(std::mem::take(&mut current));
+            current_tokens = 0;
+        }
+        current.push_str(&piece);
+        current_tokens += piece_tokens;
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the diff of one file with a hunk per entry of `hunks`, each of `lines` added lines.
+    fn file_diff(name: &str, hunks: usize, lines: usize) -> String {
+        let mut diff = format!(
+            "diff --git a/{name} b/{name}\n--- a/{name}\n+++ b/{name}\n",
+            name = name
+        );
+        for hunk in 0..hunks {
+            diff.push_str(&format!("@@ -{0},0 +{0},{1} @@\n", hunk * 100 + 1, lines));
+            for line in 0..lines {
+                diff.push_str(&format!("+line {} of hunk {}\n", line, hunk));
+            }
+        }
+        diff
+    }
+
+    #[test]
+    fn split_diff_keeps_content_that_fits() {
+        let diff = format!("{}{}", file_diff("a.rs", 1, 3), file_diff("b.rs", 1, 3));
+        assert_eq!(split_diff(&diff, u64::MAX), vec![diff]);
+    }
+
+    #[test]
+    fn split_diff_cuts_between_files() {
+        let (a, b, c) = (
+            file_diff("a.rs", 1, 10),
+            file_diff("b.rs", 1, 10),
+            file_diff("c.rs", 1, 10),
+        );
+        let diff = format!("{}{}{}", a, b, c);
+        let budget = tokens::estimate(&a) + tokens::estimate(&b);
+        let parts = split_diff(&diff, budget);
+        assert_eq!(parts, vec![format!("{}{}", a, b), c]);
+        assert_eq!(parts.concat(), diff);
+    }
+
+    #[test]
+    fn split_diff_repeats_file_header_for_each_hunk() {
+        let diff = file_diff("big.rs", 3, 20);
+        let header = "diff --git a/big.rs b/big.rs\n--- a/big.rs\n+++ b/big.rs\n";
+        // Room for one hunk, whose `@@` line grows with the line numbers
+        let budget = tokens::estimate(&file_diff("big.rs", 1, 20)) + 2;
+        let parts = split_diff(&diff, budget);
+        assert_eq!(parts.len(), 3);
+        for (index, part) in parts.iter().enumerate() {
+            assert!(part.starts_with(header));
+            assert!(part.contains(&format!("+line 0 of hunk {}\n", index)));
+            assert!(tokens::estimate(part) <= budget);
+        }
+    }
+
+    #[test]
+    fn split_diff_cuts_a_large_hunk_between_lines() {
+        let diff = file_diff("big.rs", 1, 200);
+        let parts = split_diff(&diff, 200);
+        assert!(parts.len() > 1);
+        for part in &parts {
+            assert!(part.starts_with("diff --git a/big.rs b/big.rs\n"));
+            assert!(tokens::estimate(part) <= 200);
+        }
+        let added = parts
+            .iter()
+            .flat_map(|part| part.lines())
+            .filter(|line| line.starts_with("+line"))
+            .count();
+        assert_eq!(added, 200);
+    }
+
+    #[test]
+    fn split_per_file_keeps_the_preamble_with_the_first_file() {
+        let preamble = "Changed files:\na.rs | 3 +++\nb.rs | 3 +++\n";
+        let (a, b) = (file_diff("a.rs", 1, 3), file_diff("b.rs", 1, 3));
+        let diff = format!("{}{}{}", preamble, a, b);
+        let parts = split_per_file(&diff, u64::MAX);
+        assert_eq!(parts, vec![format!("{}{}", preamble, a), b.clone()]);
+
+        // A preamble too large to share a part gets one of its own
+        let budget = tokens::estimate(&a);
+        let parts = split_per_file(&diff, budget);
+        assert_eq!(parts, vec![preamble.to_string(), a, b]);
+    }
+
+    #[test]
+    fn pack_joins_pieces_within_the_budget() {
+        let pieces = ["aaaa", "bbbb", "cccc", "dddddddddddd"].map(str::to_string);
+        assert_eq!(
+            pack(pieces.clone(), 2),
+            vec!["aaaabbbb", "cccc", "dddddddddddd"]
+        );
+        assert_eq!(pack(pieces, 100), vec!["aaaabbbbccccdddddddddddd"]);
+        assert!(pack(Vec::new(), 10).is_empty());
+    }
+
+    #[test]
+    fn count_files_counts_file_headers() {
+        let diff = format!("{}{}", file_diff("a.rs", 2, 1), file_diff("b.rs", 1, 1));
+        assert_eq!(count_files(&diff), 2);
+        assert_eq!(count_files(""), 0);
+    }
+}
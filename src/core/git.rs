@@ -22,6 +22,24 @@ pub fn get_git_root() -> Option<PathBuf> {
     }
 }
 
+/// Gets the SHA of the commit `HEAD` points to.
+///
+/// # Returns
+///
+/// * `Option<String>` containing the full SHA, `None` if there is no commit yet.
+pub fn get_head_sha() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()?;
+
+    if output.status.success() {
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        None
+    }
+}
+
 /// Checks if the current directory is a git repository.
 ///
 /// # Returns
@@ -77,6 +95,41 @@ pub fn git_add_all() {
     output::print_verbose("Run 'git add .'");
 }
 
+/// Records the current staging area as a tree object, so it can be restored later.
+///
+/// # Returns
+///
+/// * `Option<String>` containing the tree id, `None` if the index cannot be written (e.g. during a merge conflict).
+pub fn git_write_tree() -> Option<String> {
+    let output = Command::new("git").args(["write-tree"]).output().ok()?;
+
+    output::print_verbose("Run 'git write-tree'");
+
+    if output.status.success() {
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        None
+    }
+}
+
+/// Resets the staging area to a tree recorded by `git_write_tree`, leaving the working tree untouched.
+///
+/// # Arguments
+///
+/// * `tree` - The tree id to restore.
+///
+/// # Returns
+///
+/// * `true` if the staging area was restored, `false` otherwise.
+pub fn git_read_tree(tree: &str) -> bool {
+    output::print_verbose(&format!("Run 'git read-tree {}'", tree));
+
+    Command::new("git")
+        .args(["read-tree", tree])
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
 /// Gets the name-status of staged changes.
 ///
 /// # Returns
@@ -254,3 +307,132 @@ pub fn git_commit(subject: &str, message: &str, overwrite: bool) -> bool {
 
     commit_output.status.success()
 }
+
+/// Returns the editor git uses for commit messages.
+///
+/// Resolved by `git var GIT_EDITOR` from `$GIT_EDITOR`, `core.editor`, `$VISUAL` and `$EDITOR`,
+/// falling back to git's default editor.
+///
+/// # Returns
+///
+/// * `Some(editor)` with the editor command line, `None` if git cannot determine one.
+pub fn get_git_editor() -> Option<String> {
+    let output = Command::new("git")
+        .args(["var", "GIT_EDITOR"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let editor = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    output::print_verbose(&format!("Run 'git var GIT_EDITOR': {}", editor));
+    (!editor.is_empty()).then_some(editor)
+}
+
+/// Returns the directory git runs the hooks of the repository from.
+///
+/// Resolved by `git rev-parse --git-path hooks`, which honors `core.hooksPath`. A relative
+/// `core.hooksPath` is taken relative to the repository root, where git runs hooks.
+///
+/// # Returns
+///
+/// * `Some(PathBuf)` with the hooks directory, `None` outside a git repository.
+pub fn get_hooks_dir() -> Option<PathBuf> {
+    let root = get_git_root()?;
+    let output = Command::new("git")
+        .args(["rev-parse", "--git-path", "hooks"])
+        .current_dir(&root)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let hooks = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    output::print_verbose(&format!(
+        "Run 'git rev-parse --git-path hooks': {}",
+        hooks.display()
+    ));
+    Some(root.join(hooks))
+}
+
+/// Returns the character starting comment lines in commit messages.
+///
+/// # Returns
+///
+/// * The `core.commentChar` setting, `#` if it is not set or set to `auto`.
+pub fn get_comment_char() -> String {
+    Command::new("git")
+        .args(["config", "core.commentChar"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|comment| !comment.is_empty() && comment != "auto")
+        .unwrap_or_else(|| "#".to_string())
+}
+
+/// Lets the user edit a commit message in the git editor, like `git commit -e`.
+///
+/// The message is written to `COMMIT_EDITMSG` in the git directory together with a hint in
+/// comment lines. On return, comment lines are stripped, runs of blank lines collapsed and
+/// surrounding blank lines trimmed, as git's default cleanup does; `core.commentChar` is honored.
+///
+/// # Arguments
+///
+/// * `message` - The message to start from, the subject on the first line.
+///
+/// # Returns
+///
+/// * `Ok(edited)` with the cleaned up message, empty if the user removed everything.
+/// * `Err` if the file cannot be written or the editor fails.
+pub fn git_edit_message(message: &str) -> std::io::Result<String> {
+    let editor = get_git_editor()
+        .ok_or_else(|| std::io::Error::other("no editor configured, set core.editor or $EDITOR"))?;
+    let path_output = Command::new("git")
+        .args(["rev-parse", "--git-path", "COMMIT_EDITMSG"])
+        .output()?;
+    let path = String::from_utf8_lossy(&path_output.stdout)
+        .trim()
+        .to_string();
+    let comment = get_comment_char();
+    std::fs::write(
+        &path,
+        format!(
+            "{}\n\n{c} Please edit the commit message generated by gim. Lines starting\n{c} with '{c}' will be ignored, and an empty message aborts the commit.\n",
+            message.trim_end(),
+            c = comment
+        ),
+    )?;
+
+    // The editor may carry arguments, run it through the shell like git does
+    let status = if cfg!(windows) {
+        Command::new("cmd")
+            .args(["/C", &format!("{} \"{}\"", editor, path)])
+            .status()?
+    } else {
+        Command::new("sh")
+            .args(["-c", &format!("{} \"$@\"", editor), &editor, &path])
+            .status()?
+    };
+    if !status.success() {
+        return Err(std::io::Error::other(format!(
+            "there was a problem with the editor '{}'",
+            editor
+        )));
+    }
+
+    let edited = std::fs::read_to_string(&path)?;
+    let mut lines: Vec<&str> = Vec::new();
+    for line in edited
+        .lines()
+        .filter(|line| !line.starts_with(comment.as_str()))
+        .map(str::trim_end)
+    {
+        // Runs of blank lines collapse into one, as with `--cleanup=strip`
+        if line.is_empty() && lines.last().is_some_and(|last| last.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    Ok(lines.join("\n").trim().to_string())
+}
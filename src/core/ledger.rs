@@ -0,0 +1,83 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use chrono::{DateTime, Local};
+use gim_config::directory;
+use serde::{Deserialize, Serialize};
+
+use crate::config::constants::USAGE_LEDGER_FILE;
+use crate::core::ai::types::Usage;
+use crate::utils::output;
+
+/// One AI request recorded in the usage ledger.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entry {
+    pub time: DateTime<Local>,
+    /// The root of the repository the commit message was generated for, empty outside a repository.
+    pub repo: String,
+    pub provider: String,
+    pub model: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Estimated spend at the prices configured when the request was made, `None` if no price matched.
+    pub cost: Option<f64>,
+    /// Whether the token counts are estimated because the provider did not report them.
+    #[serde(default)]
+    pub estimated: bool,
+}
+
+impl Entry {
+    /// Returns the token counts of the entry.
+    pub fn usage(&self) -> Usage {
+        Usage {
+            prompt_tokens: self.prompt_tokens,
+            completion_tokens: self.completion_tokens,
+        }
+    }
+}
+
+/// Returns the path of the ledger file in the config directory.
+fn ledger_path() -> io::Result<PathBuf> {
+    Ok(directory::config_dir()?.join(USAGE_LEDGER_FILE))
+}
+
+/// Appends an entry to the ledger, one JSON object per line.
+///
+/// # Arguments
+///
+/// * `entry` - The entry to record.
+pub fn append(entry: &Entry) -> io::Result<()> {
+    let path = ledger_path()?;
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    // One write per entry, so concurrent requests appending at once cannot interleave a line
+    file.write_all(&line)?;
+    output::print_verbose(&format!("Recorded ai usage in {}", path.display()));
+    Ok(())
+}
+
+/// Reads all entries of the ledger in the order they were recorded.
+///
+/// # Returns
+///
+/// * `Ok(Vec<Entry>)`, empty if nothing was recorded yet. Unreadable lines are skipped.
+pub fn read_entries() -> io::Result<Vec<Entry>> {
+    let path = ledger_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path)?;
+    Ok(content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                output::print_verbose(&format!("Skip unreadable ledger line '{}': {}", line, e));
+                None
+            }
+        })
+        .collect())
+}
@@ -0,0 +1,228 @@
+use indoc::indoc;
+use serde::{Deserialize, Serialize};
+
+/// Name of the JSON schema of a commit message, sent to providers with a JSON mode.
+pub const SCHEMA_NAME: &str = "commit_message";
+
+/// A conventional commit message as answered by the AI in structured mode.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CommitMessage {
+    /// The commit type, e.g. `feat` or `fix`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// The part of the code base the change is about, if any.
+    #[serde(default)]
+    pub scope: Option<String>,
+    pub subject: String,
+    /// A short explanation of the change, may be empty.
+    #[serde(default)]
+    pub body: String,
+    /// Whether the change breaks compatibility.
+    #[serde(default)]
+    pub breaking: bool,
+}
+
+impl CommitMessage {
+    /// Returns the JSON schema an answer in structured mode must match.
+    pub fn schema() -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "type": { "type": "string" },
+                "scope": { "type": ["string", "null"] },
+                "subject": { "type": "string" },
+                "body": { "type": "string" },
+                "breaking": { "type": "boolean" },
+            },
+            "required": ["type", "scope", "subject", "body", "breaking"],
+            "additionalProperties": false,
+        })
+    }
+
+    /// Returns the instruction appended to the subject prompt in structured mode.
+    ///
+    /// Providers without a JSON mode only see this instruction, so it spells out the fields.
+    pub fn instruction() -> &'static str {
+        indoc! {r#"
+
+            Answer with a JSON object only, with these keys:
+            - "type": the commit {type}
+            - "scope": the part of the code base the changes are about, or null
+            - "subject": the {commit_message}, without the type
+            - "body": at most two sentences explaining why the changes were made, or ""
+            - "breaking": true if the changes break compatibility, otherwise false
+        "#}
+    }
+
+    /// Returns the instruction joining the diff and subject prompts in single-request mode.
+    ///
+    /// The `body` then carries the file summaries the diff prompt asks for.
+    pub fn single_request_instruction() -> &'static str {
+        indoc! {r#"
+
+            You are given the changes directly, do both tasks above in one answer.
+            Ignore the output formats above and answer with a JSON object only, with these keys:
+            - "type": the commit {type}
+            - "scope": the part of the code base the changes are about, or null
+            - "subject": the {commit_message}, without the type
+            - "body": the summary lines of the changed files, one per line, following the format and rules for summaries above
+            - "breaking": true if the changes break compatibility, otherwise false
+        "#}
+    }
+
+    /// Parses the answer of the AI.
+    ///
+    /// A JSON object is looked for first, also inside markdown fences or after a preamble. Answers
+    /// of providers without a JSON mode fall back to the first line in the
+    /// `type(scope)!: subject` format, with quotes and backticks stripped.
+    ///
+    /// # Arguments
+    ///
+    /// * `answer` - The answer text.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(CommitMessage)` if the answer could be parsed, `None` otherwise.
+    pub fn parse(answer: &str) -> Option<CommitMessage> {
+        let text = answer
+            .lines()
+            .filter(|line| !line.trim_start().starts_with("```"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if let (Some(start), Some(end)) = (text.find('{'), text.rfind('}'))
+            && start < end
+            && let Ok(message) = serde_json::from_str::<CommitMessage>(&text[start..=end])
+            && !message.kind.trim().is_empty()
+            && !message.subject.trim().is_empty()
+        {
+            return Some(message);
+        }
+        text.lines().find_map(Self::parse_header)
+    }
+
+    /// Parses a `type(scope)!: subject` line.
+    fn parse_header(line: &str) -> Option<CommitMessage> {
+        let line = line.trim().trim_matches(['"', '\'', '`']).trim();
+        let (head, subject) = line.split_once(':')?;
+        let subject = subject.trim();
+        let (head, breaking) = match head.strip_suffix('!') {
+            Some(head) => (head, true),
+            None => (head, false),
+        };
+        let (kind, scope) = match head.split_once('(') {
+            Some((kind, scope)) => (kind, Some(scope.strip_suffix(')')?.to_string())),
+            None => (head, None),
+        };
+        if subject.is_empty() || kind.is_empty() || !kind.chars().all(|c| c.is_ascii_lowercase()) {
+            return None;
+        }
+        Some(CommitMessage {
+            kind: kind.to_string(),
+            scope,
+            subject: subject.to_string(),
+            body: String::new(),
+            breaking,
+        })
+    }
+
+    /// Returns the first line of the commit, e.g. `feat(cli)!: add the --print option`.
+    pub fn header(&self) -> String {
+        let scope = self
+            .scope
+            .as_deref()
+            .map(str::trim)
+            .filter(|scope| !scope.is_empty())
+            .map_or(String::new(), |scope| format!("({})", scope));
+        format!(
+            "{}{}{}: {}",
+            self.kind.trim(),
+            scope,
+            if self.breaking { "!" } else { "" },
+            self.subject.trim()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_json_inside_markdown_fences() {
+        let answer = indoc! {r#"
+            ```json
+            {"type": "feat", "scope": "cli", "subject": "add json mode", "body": "Scripts need it.", "breaking": true}
+            ```
+        "#};
+        let message = CommitMessage::parse(answer).unwrap();
+        assert_eq!(message.kind, "feat");
+        assert_eq!(message.scope.as_deref(), Some("cli"));
+        assert_eq!(message.body, "Scripts need it.");
+        assert_eq!(message.header(), "feat(cli)!: add json mode");
+    }
+
+    #[test]
+    fn parse_json_after_preamble() {
+        let answer = r#"Here is the commit message: {"type": "fix", "scope": null, "subject": "handle empty diffs"}"#;
+        let message = CommitMessage::parse(answer).unwrap();
+        assert_eq!(message.scope, None);
+        assert!(!message.breaking);
+        assert_eq!(message.header(), "fix: handle empty diffs");
+    }
+
+    #[test]
+    fn parse_falls_back_to_header_line() {
+        let answer = "Sure, here it is:\nrefactor(core)!: split the provider module\n";
+        let message = CommitMessage::parse(answer).unwrap();
+        assert_eq!(message.kind, "refactor");
+        assert_eq!(message.scope.as_deref(), Some("core"));
+        assert!(message.breaking);
+        assert_eq!(message.subject, "split the provider module");
+        assert!(message.body.is_empty());
+    }
+
+    #[test]
+    fn parse_falls_back_when_json_lacks_subject() {
+        let answer = "{\"type\": \"feat\", \"subject\": \" \"}\nfeat: add candidates";
+        let message = CommitMessage::parse(answer).unwrap();
+        assert_eq!(message.header(), "feat: add candidates");
+    }
+
+    #[test]
+    fn parse_rejects_free_text() {
+        assert_eq!(CommitMessage::parse("Add a new option"), None);
+        assert_eq!(CommitMessage::parse("Note: this adds an option"), None);
+        assert_eq!(CommitMessage::parse(""), None);
+    }
+
+    #[test]
+    fn parse_header_strips_quotes() {
+        for line in [
+            "\"docs: explain the cache\"",
+            "'docs: explain the cache'",
+            "`docs: explain the cache`",
+        ] {
+            let message = CommitMessage::parse_header(line).unwrap();
+            assert_eq!(message.header(), "docs: explain the cache");
+        }
+    }
+
+    #[test]
+    fn parse_header_scope_and_breaking_mark() {
+        let message = CommitMessage::parse_header("feat(ai)!: drop the old client").unwrap();
+        assert_eq!(message.scope.as_deref(), Some("ai"));
+        assert!(message.breaking);
+
+        let message = CommitMessage::parse_header("chore!: bump the edition").unwrap();
+        assert_eq!(message.scope, None);
+        assert!(message.breaking);
+    }
+
+    #[test]
+    fn parse_header_rejects_malformed_lines() {
+        assert_eq!(CommitMessage::parse_header("feat(ai: unclosed scope"), None);
+        assert_eq!(CommitMessage::parse_header("Feat: capitalized type"), None);
+        assert_eq!(CommitMessage::parse_header("feat:"), None);
+        assert_eq!(CommitMessage::parse_header(": no type"), None);
+    }
+}
@@ -1,3 +1,6 @@
 pub mod ai;
+pub mod cache;
 pub mod diff;
 pub mod git;
+pub mod ledger;
+pub mod message;
@@ -1,8 +1,14 @@
-use cli::{GimCli, GimCommands};
-use commands::{ai as commands_ai, commit, config as commands_config, prompt, update};
-use core::{ai::client, diff, git};
+use cli::{AiCommands, CacheCommands, GimCli, GimCommands, HookCommands, OutputFormat};
+use commands::commit::Review;
+use commands::{
+    ai as commands_ai, budget as commands_budget, cache as commands_cache, commit,
+    config as commands_config, hook as commands_hook, prompt, stats as commands_stats, update,
+};
+use core::ai::error::AiError;
+use core::{diff, git};
 use gim_config::config::get_config;
 use std::env;
+use utils::report;
 
 mod cli;
 mod commands;
@@ -15,14 +21,20 @@ async fn main() {
     let cli = <GimCli as clap::Parser>::parse();
 
     // Set global flags
+    let json = cli.format == OutputFormat::Json;
     utils::output::set_quiet(cli.quiet);
+    // stdout carries only the message of --print or the JSON report
+    utils::output::set_stderr(cli.print || json);
+    report::set_enabled(json);
     utils::output::set_verbose(cli.verbose);
+    commands_budget::set_ignored(cli.ignore_budget);
+    commands_cache::set_disabled(cli.no_cache);
+    commands_ai::set_candidates(cli.candidates);
 
     let start_time = std::time::Instant::now();
     // Start update reminder check asynchronously in background
     // Skip if --dry flag is passed or if this is the update subcommand
-    let update_check_handle = if !cli.dry && env::args().nth(1).map_or(true, |arg| arg != "update")
-    {
+    let update_check_handle = if !cli.dry && env::args().nth(1).is_none_or(|arg| arg != "update") {
         Some(tokio::spawn(update::check_update_reminder_async()))
     } else {
         None
@@ -32,7 +44,15 @@ async fn main() {
     let config = get_config().expect("Failed to access config file");
     if let Err(e) = run_cli(&cli, config).await {
         eprintln!("Error: {}", e);
-        std::process::exit(1);
+        // AI failures carry their own exit code so scripts can tell them apart
+        let code = e.downcast_ref::<AiError>().map_or(1, AiError::exit_code);
+        if json {
+            report::print(Some((e.to_string(), code)));
+        }
+        std::process::exit(code);
+    }
+    if json {
+        report::print(None);
     }
     utils::output::print_normal("");
     utils::output::print_promotion();
@@ -64,7 +84,7 @@ async fn run_cli(cli: &GimCli, mut config: toml::Value) -> Result<(), Box<dyn st
                 }
                 'max: {
                     if let Some(max) = max {
-                        if *max <= 0 {
+                        if *max == 0 {
                             eprintln!("Error: --max must be a positive integer");
                             break 'max;
                         }
@@ -76,7 +96,7 @@ async fn run_cli(cli: &GimCli, mut config: toml::Value) -> Result<(), Box<dyn st
                 }
                 'interval: {
                     if let Some(interval) = interval {
-                        if *interval <= 0 {
+                        if *interval == 0 {
                             eprintln!("Error: --interval must be a positive integer");
                             break 'interval;
                         }
@@ -88,8 +108,7 @@ async fn run_cli(cli: &GimCli, mut config: toml::Value) -> Result<(), Box<dyn st
                 }
             } else {
                 if let Err(e) = update::check_and_install_update(*force).await {
-                    eprintln!("Failed to update: {}", e);
-                    std::process::exit(1);
+                    return Err(format!("Failed to update: {}", e).into());
                 }
             }
             return Ok(());
@@ -108,96 +127,146 @@ async fn run_cli(cli: &GimCli, mut config: toml::Value) -> Result<(), Box<dyn st
                 }
                 // delete the 2 files
                 if let Err(e) = prompt::delete_prompt_files() {
-                    eprintln!("Error in reset prompt: {}", e);
-                    std::process::exit(1);
+                    return Err(format!("Failed to reset prompt: {}", e).into());
                 }
-            } else if let Err(e) =
-                prompt::handle_prompt_command(*edit, prompt.as_deref(), editor.as_deref())
-            {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
+            } else {
+                prompt::handle_prompt_command(*edit, prompt.as_deref(), editor.as_deref())?;
             }
             return Ok(());
         }
         Some(GimCommands::Ai {
+            action,
             model,
             apikey,
             url,
             language,
+            stream,
         }) => {
+            if let Some(AiCommands::Providers) = action {
+                commands_ai::print_providers();
+                return Ok(());
+            }
+
             // Check if -k is used without a value (empty string means flag was provided but no value)
-            if let Some(apikey_val) = apikey {
-                if apikey_val.is_empty() {
-                    // User ran: gim ai -k (without value)
-                    // Show current API key (full, not masked)
-                    let ai = commands_ai::get_validated_ai_config(false, false);
-                    if let Some(ai) = ai {
-                        utils::output::print_normal(&format!("Current API Key: {}", &ai.2));
-                    } else {
-                        eprintln!("Error: ai section is not configured");
-                    }
-                    return Ok(());
-                }
+            if let Some(apikey_val) = apikey
+                && apikey_val.is_empty()
+            {
+                // User ran: gim ai -k (without value)
+                // Show current API key (full, not masked)
+                let ai = commands_ai::get_validated_ai_config()?;
+                utils::output::print_normal(&format!("Current API Key: {}", &ai.2));
+                report::set_field("ai", "apikey", &ai.2);
+                return Ok(());
             }
 
-            if model.is_none() && apikey.is_none() && url.is_none() && language.is_none() {
-                let ai = commands_ai::get_validated_ai_config(false, false);
-                if let Some(ai) = ai {
-                    let mut url = ai.0;
-                    if url.is_empty() && !ai.1.is_empty() {
-                        if let Some(str) = client::get_url_by_model(&ai.1) {
-                            url = format!("(not configured. Will use default : {})", str);
-                        } else {
-                            eprintln!("Warning: you have not setup api url by 'gim ai -u <url>'");
+            if model.is_none()
+                && apikey.is_none()
+                && url.is_none()
+                && language.is_none()
+                && stream.is_none()
+            {
+                match commands_ai::get_validated_ai_config() {
+                    Ok(ai) => {
+                        let mut url = ai.0;
+                        if url.is_empty() && !ai.1.is_empty() {
+                            if let Some((provider, str)) =
+                                commands_ai::get_provider_registry().resolve(&ai.1, "")
+                            {
+                                url = format!(
+                                    "(not configured. Will use default of '{}': {})",
+                                    provider.name(),
+                                    str
+                                );
+                            } else {
+                                eprintln!(
+                                    "Warning: you have not setup api url by 'gim ai -u <url>'"
+                                );
+                            }
                         }
-                    }
-                    let masked_key = commands_ai::mask_api_key(&ai.2);
-                    indoc::printdoc!(
-                        r#"
+                        let masked_key = commands_ai::mask_api_key(&ai.2);
+                        let fallbacks = commands_ai::get_fallback_models();
+                        report::set(
+                            "ai",
+                            serde_json::json!({
+                                "model": &ai.1,
+                                "apikey": &masked_key,
+                                "url": &url,
+                                "language": &ai.3,
+                                "stream": commands_ai::get_stream_enabled(),
+                                "fallbacks": &fallbacks,
+                            }),
+                        );
+                        let fallbacks = if fallbacks.is_empty() {
+                            "(none)".to_string()
+                        } else {
+                            fallbacks.join(" -> ")
+                        };
+                        utils::output::print_block(&indoc::formatdoc!(
+                            r#"
                         Model:      {}
                         API Key:    {}
                         URL:        {}
                         Language:   {}
-                        You can use 'gim ai -m <model> -k <apikey> -u <url> -l <language>' respectively to update the configuration
+                        Stream:     {}
+                        Fallbacks:  {}
+                        You can use 'gim ai -m <model> -k <apikey> -u <url> -l <language> --stream <true|false>' respectively to update the configuration
                         "#,
-                        &ai.1,
-                        &masked_key,
-                        &url,
-                        &ai.3
-                    );
-                } else {
-                    eprintln!("Error: ai section is not configured");
+                            &ai.1,
+                            &masked_key,
+                            &url,
+                            &ai.3,
+                            commands_ai::get_stream_enabled(),
+                            fallbacks
+                        ));
+                    }
+                    Err(e) => return Err(e.into()),
                 }
                 return Ok(());
             }
-            commands_ai::update_ai_config(&mut config, model, apikey, url, language);
+            commands_ai::update_ai_config(&mut config, model, apikey, url, language, stream);
             return Ok(());
         }
         Some(GimCommands::Config {
             lines_limit,
             max_files,
+            review,
             show_location,
         }) => {
             if *show_location {
-                if let Err(e) = commands_config::get_config_and_print() {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
-                }
+                commands_config::get_config_and_print()?;
                 if let Err(e) = prompt::open_config_directory() {
                     eprintln!("Error: {}", e);
                 }
             }
             if let Some(lines_limit) = lines_limit {
-                if let Err(e) = commands_config::set_lines_limit(*lines_limit) {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
-                }
+                commands_config::set_lines_limit(*lines_limit)?;
+                report::set_field("config", "lines_limit", lines_limit);
             }
             if let Some(max_files) = max_files {
-                if let Err(e) = commands_config::set_max_diff_files(*max_files) {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
-                }
+                commands_config::set_max_diff_files(*max_files)?;
+                report::set_field("config", "max_files", max_files);
+            }
+            if let Some(review) = review {
+                commands_config::set_review_enabled(*review)?;
+                report::set_field("config", "review", review);
+            }
+            return Ok(());
+        }
+        Some(GimCommands::Stats { by, last, all }) => {
+            commands_stats::print_stats(*by, *last, *all)?;
+            return Ok(());
+        }
+        Some(GimCommands::Cache { action }) => {
+            match action {
+                CacheCommands::Clear => commands_cache::clear_cache()?,
+            }
+            return Ok(());
+        }
+        Some(GimCommands::Hook { action }) => {
+            match action {
+                HookCommands::Install => commands_hook::install_hook()?,
+                HookCommands::Uninstall => commands_hook::uninstall_hook()?,
+                HookCommands::Status => commands_hook::print_hook_status()?,
             }
             return Ok(());
         }
@@ -207,6 +276,7 @@ async fn run_cli(cli: &GimCli, mut config: toml::Value) -> Result<(), Box<dyn st
     // Check if current directory is a git repository
     if !git::is_git_repo() {
         utils::output::print_warning("The current directory is not a git repository.");
+        report::set("skipped", "not a git repository");
         return Ok(());
     }
 
@@ -216,9 +286,16 @@ async fn run_cli(cli: &GimCli, mut config: toml::Value) -> Result<(), Box<dyn st
     // Determine max_files value: CLI arg > config file > default
     let max_files = cli
         .max_files
-        .unwrap_or_else(|| commands_config::get_max_diff_files());
+        .unwrap_or_else(commands_config::get_max_diff_files);
     utils::output::print_verbose(&format!("Using max_files limit: {}", max_files));
 
+    // Remember the staging area, auto-add changes it before the AI is asked
+    let index_tree = if cli.auto_add {
+        git::git_write_tree()
+    } else {
+        None
+    };
+
     // Build diff content
     let changes_ref: Vec<&str> = changes.iter().map(|s| s.as_str()).collect();
     let diff_content =
@@ -226,6 +303,7 @@ async fn run_cli(cli: &GimCli, mut config: toml::Value) -> Result<(), Box<dyn st
 
     if diff_content.is_empty() {
         utils::output::print_normal("No changes to commit.");
+        report::set("skipped", "no changes to commit");
         return Ok(());
     }
 
@@ -235,36 +313,132 @@ async fn run_cli(cli: &GimCli, mut config: toml::Value) -> Result<(), Box<dyn st
             "\n--- DRY RUN ---\nContent to be sent to AI:\n{}",
             diff_content
         ));
+        report::set("diff", &diff_content);
         return Ok(());
     }
 
-    // Check diff limit
-    let diff_limit = commands_config::get_lines_limit();
-    commit::check_diff_limit(&diff_content, diff_limit)?;
-
     // Get AI config
-    let config_result = commands_ai::get_validated_ai_config(cli.auto_add, changes.len() > 0);
-    if config_result.is_none() {
+    let (url, model_name, api_key, language) = commands_ai::get_validated_ai_config()?;
+    let endpoints = commands_ai::get_endpoints(url, model_name, api_key)?;
+
+    // Keep the models whose context windows the changes fit into
+    let endpoints =
+        commit::fit_context_budget(&diff_content, endpoints, cli.diff_prompt.as_deref());
+
+    // Generate commit message and let the user review it, Ctrl+C drops the in-flight request
+    // Nothing is committed with --print or --output, so there is nothing to review
+    let review = !cli.yes
+        && !cli.print
+        && cli.output.is_none()
+        && commands_config::get_review_enabled()
+        && utils::input::is_interactive();
+    let generation = async {
+        let (mut subject, mut message, file_changes) = commit::generate_commit_message(
+            diff_content.clone(),
+            &endpoints,
+            language.clone(),
+            cli.verbose,
+            cli.title.clone(),
+            cli.diff_prompt.clone(),
+            cli.subject_prompt.clone(),
+        )
+        .await?;
+        let mut regenerations = 0;
+        if cli.edit {
+            // The editor is the review, like 'git commit -e'
+            (subject, message) = commit::edit_commit_message(&subject, &message)?;
+        } else if review {
+            loop {
+                match commit::ask_review().await? {
+                    Review::Accept => break,
+                    Review::Edit => {
+                        (subject, message) = commit::edit_commit_message(&subject, &message)?;
+                        commit::print_commit_message(&subject, &message);
+                    }
+                    Review::Regenerate if cli.title.is_some() => {
+                        utils::output::print_warning(
+                            "The subject was given by --title, use [e]dit or [f]eedback to change it",
+                        );
+                    }
+                    Review::Regenerate => {
+                        regenerations += 1;
+                        (subject, message) = commit::regenerate_subject(
+                            &endpoints,
+                            &file_changes,
+                            regenerations,
+                            cli.verbose,
+                            cli.subject_prompt.as_deref(),
+                        )
+                        .await?;
+                        commit::print_commit_message(&subject, &message);
+                    }
+                    Review::Refine(feedback) => {
+                        (subject, message) = commit::refine_subject(
+                            &endpoints,
+                            &subject,
+                            &message,
+                            &file_changes,
+                            &feedback,
+                            cli.verbose,
+                            cli.subject_prompt.as_deref(),
+                        )
+                        .await?;
+                        commit::print_commit_message(&subject, &message);
+                    }
+                }
+            }
+        }
+        Ok::<_, Box<dyn std::error::Error>>((subject, message))
+    };
+    let res = tokio::select! {
+        res = generation => res,
+        _ = tokio::signal::ctrl_c() => {
+            utils::output::print_normal("");
+            Err(AiError::Cancelled.into())
+        }
+    };
+    // Tokio keeps its SIGINT handler for the rest of the process, so Ctrl+C no longer
+    // terminates by default; exit like the default would while committing and waiting
+    tokio::spawn(async {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
+        }
+    });
+    let (subject, message) = match res {
+        Ok(generated) => generated,
+        Err(e) => {
+            // Nothing is committed, unstage what auto-add staged
+            if let Some(tree) = index_tree
+                && !git::git_read_tree(&tree)
+            {
+                utils::output::print_warning(
+                    "Failed to restore the staging area, run 'git status' to check it",
+                );
+            }
+            return Err(e);
+        }
+    };
+
+    report::set(
+        "message",
+        serde_json::json!({ "subject": &subject, "body": &message }),
+    );
+
+    // Hand the message over instead of committing, e.g. to 'git commit -F'
+    if cli.print || cli.output.is_some() {
+        let text = format!("{}\n\n{}\n", subject, message);
+        if let Some(path) = &cli.output {
+            std::fs::write(path, &text)?;
+            utils::output::print_normal(&format!("Wrote the commit message to {}", path.display()));
+            report::set("output", path);
+        }
+        if cli.print && !report::is_enabled() {
+            // The only output on stdout, everything else went to stderr
+            print!("{}", text);
+        }
         return Ok(());
     }
-    let (url, model_name, api_key, language) = config_result.unwrap();
-
-    // Generate commit message
-    let (subject, message) = commit::generate_commit_message(
-        diff_content,
-        url,
-        model_name,
-        api_key,
-        language,
-        cli.verbose,
-        cli.title.clone(),
-        cli.diff_prompt.clone(),
-        cli.subject_prompt.clone(),
-    )
-    .await?;
 
     // Execute commit
-    commit::execute_commit(&subject, &message, cli.overwrite);
-
-    Ok(())
+    commit::execute_commit(&subject, &message, cli.overwrite)
 }
@@ -0,0 +1,36 @@
+use std::io::{self, IsTerminal, Write};
+
+/// Returns whether the user can answer questions, i.e. stdin and stderr are terminals.
+///
+/// # Returns
+///
+/// * `bool` indicating whether interactive prompts can be shown.
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal() && io::stderr().is_terminal()
+}
+
+/// Asks the user a question on stderr and reads the answer from stdin.
+///
+/// The line is read on a blocking thread, so Ctrl+C still cancels the surrounding task.
+///
+/// # Arguments
+///
+/// * `question` - The question to print before the cursor.
+///
+/// # Returns
+///
+/// * `Ok(answer)` with surrounding whitespace trimmed.
+/// * `Err` with `UnexpectedEof` if stdin is closed.
+pub async fn ask(question: &str) -> io::Result<String> {
+    eprint!("{}", question);
+    io::stderr().flush()?;
+    tokio::task::spawn_blocking(|| {
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line)? == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+        Ok(line.trim().to_string())
+    })
+    .await
+    .map_err(io::Error::other)?
+}
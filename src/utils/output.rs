@@ -1,9 +1,11 @@
 use lazy_static::lazy_static;
+use std::io::Write;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 lazy_static! {
     static ref VERBOSE: AtomicBool = AtomicBool::new(false);
     static ref QUIET: AtomicBool = AtomicBool::new(false);
+    static ref TO_STDERR: AtomicBool = AtomicBool::new(false);
 }
 
 /// Sets the global verbose flag.
@@ -23,6 +25,22 @@ pub fn set_verbose(verbose: bool) {
 pub fn set_quiet(quiet: bool) {
     QUIET.store(quiet, Ordering::Relaxed);
 }
+/// Sets whether all output goes to stderr, keeping stdout for the result of the command.
+///
+/// # Arguments
+///
+/// * `to_stderr` - If true, messages are printed to stderr instead of stdout.
+pub fn set_stderr(to_stderr: bool) {
+    TO_STDERR.store(to_stderr, Ordering::Relaxed);
+}
+/// Prints a line to stdout, or to stderr if output is redirected there.
+fn emit(message: &str) {
+    if TO_STDERR.load(Ordering::Relaxed) {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
+    }
+}
 /// Returns the current value of the global verbose flag.
 ///
 /// # Returns
@@ -46,7 +64,7 @@ pub fn is_quiet() -> bool {
 /// * `message` - The message to print if not in quiet mode.
 pub fn print_normal(message: &str) {
     if !is_quiet() {
-        println!("{}", message);
+        emit(message);
     }
 }
 /// Prints a message if verbose mode is enabled and quiet mode is not.
@@ -56,7 +74,25 @@ pub fn print_normal(message: &str) {
 /// * `message` - The message to print if verbose mode is enabled.
 pub fn print_verbose(message: &str) {
     if is_verbose() && !is_quiet() {
-        println!("[VERBOSE] {}", message);
+        emit(&format!("[VERBOSE] {}", message));
+    }
+}
+
+/// Prints a piece of streamed text without a trailing newline unless in quiet mode.
+///
+/// # Arguments
+///
+/// * `text` - The text to append to the current line.
+pub fn print_stream(text: &str) {
+    if is_quiet() {
+        return;
+    }
+    if TO_STDERR.load(Ordering::Relaxed) {
+        eprint!("{}", text);
+        let _ = std::io::stderr().flush();
+    } else {
+        print!("{}", text);
+        let _ = std::io::stdout().flush();
     }
 }
 
@@ -74,6 +110,19 @@ pub fn print_warning(message: &str) {
 /// Prints promotion message for Rudist Redis client.
 pub fn print_promotion() {
     if !is_quiet() {
-        println!("\n🚀 Give AI-powered Redis GUI a try! https://redis-egui-client.pages.dev/");
+        emit("\n🚀 Give AI-powered Redis GUI a try! https://redis-egui-client.pages.dev/");
+    }
+}
+
+/// Prints a block of text as is, even in quiet mode.
+///
+/// # Arguments
+///
+/// * `text` - The text to print, including its trailing newline.
+pub fn print_block(text: &str) {
+    if TO_STDERR.load(Ordering::Relaxed) {
+        eprint!("{}", text);
+    } else {
+        print!("{}", text);
     }
 }
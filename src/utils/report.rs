@@ -0,0 +1,109 @@
+use lazy_static::lazy_static;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+lazy_static! {
+    static ref ENABLED: AtomicBool = AtomicBool::new(false);
+    static ref REPORT: Mutex<Map<String, Value>> = Mutex::new(Map::new());
+}
+
+/// Sets whether the results of the command are collected and printed as JSON at the end.
+///
+/// # Arguments
+///
+/// * `enabled` - If true, `set` and `push` record their values.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns whether results are reported as JSON.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Records a result, replacing an earlier value of the same key.
+///
+/// # Arguments
+///
+/// * `key` - The field of the JSON report.
+/// * `value` - The value to record.
+pub fn set(key: &str, value: impl Serialize) {
+    if !is_enabled() {
+        return;
+    }
+    let value = serde_json::to_value(value).unwrap_or(Value::Null);
+    if let Ok(mut report) = REPORT.lock() {
+        report.insert(key.to_string(), value);
+    }
+}
+
+/// Records a field of an object result, e.g. one of the prompts used.
+///
+/// # Arguments
+///
+/// * `key` - The field of the JSON report holding the object.
+/// * `field` - The field of the object.
+/// * `value` - The value to record.
+pub fn set_field(key: &str, field: &str, value: impl Serialize) {
+    if !is_enabled() {
+        return;
+    }
+    let value = serde_json::to_value(value).unwrap_or(Value::Null);
+    if let Ok(mut report) = REPORT.lock() {
+        match report
+            .entry(key.to_string())
+            .or_insert_with(|| Value::Object(Map::new()))
+        {
+            Value::Object(fields) => {
+                fields.insert(field.to_string(), value);
+            }
+            other => *other = serde_json::json!({ field: value }),
+        }
+    }
+}
+
+/// Appends a result to the list of a key, e.g. one entry per AI request.
+///
+/// # Arguments
+///
+/// * `key` - The field of the JSON report holding the list.
+/// * `value` - The value to append.
+pub fn push(key: &str, value: impl Serialize) {
+    if !is_enabled() {
+        return;
+    }
+    let value = serde_json::to_value(value).unwrap_or(Value::Null);
+    if let Ok(mut report) = REPORT.lock() {
+        match report
+            .entry(key.to_string())
+            .or_insert_with(|| Value::Array(Vec::new()))
+        {
+            Value::Array(values) => values.push(value),
+            other => *other = Value::Array(vec![value]),
+        }
+    }
+}
+
+/// Prints the collected results to stdout as one JSON object.
+///
+/// # Arguments
+///
+/// * `error` - The error message and exit code if the command failed.
+pub fn print(error: Option<(String, i32)>) {
+    let Ok(mut report) = REPORT.lock() else {
+        return;
+    };
+    report.insert("ok".to_string(), Value::Bool(error.is_none()));
+    if let Some((message, code)) = error {
+        report.insert(
+            "error".to_string(),
+            serde_json::json!({ "message": message, "code": code }),
+        );
+    }
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&*report).unwrap_or_default()
+    );
+}